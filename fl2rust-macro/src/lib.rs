@@ -1,27 +1,31 @@
 #![doc = include_str!("../README.md")]
+#![allow(clippy::needless_doctest_main)]
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use fluid_parser::lexer::Lexer;
-use fluid_parser::parser::Parser;
+
+fn expand(source: &str) -> TokenStream {
+    match fl2rust::Generator::default().generate_tokens(source) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => format!("compile_error!({:?});", e.to_string())
+            .parse()
+            .unwrap(),
+    }
+}
 
 #[proc_macro]
 pub fn include_ui(input: TokenStream) -> TokenStream {
     let input = input.to_string();
-    let input = std::fs::read_to_string(&input[1..input.len() - 1]).unwrap();
-    let lexer = Lexer::new(&input);
-    let mut parser = Parser::new(lexer);
-    let ast = parser.parse();
-    let out = fl2rust::gen::generate(&ast);
-    out.parse().unwrap()
+    let path = &input[1..input.len() - 1];
+    match std::fs::read_to_string(path) {
+        Ok(source) => expand(&source),
+        Err(e) => format!("compile_error!({:?});", format!("{}: {}", path, e))
+            .parse()
+            .unwrap(),
+    }
 }
 
 #[proc_macro]
 pub fn build_ui(input: TokenStream) -> TokenStream {
-    let input = input.to_string();
-    let lexer = Lexer::new(&input);
-    let mut parser = Parser::new(lexer);
-    let ast = parser.parse();
-    let out = fl2rust::gen::generate(&ast);
-    out.parse().unwrap()
-}
\ No newline at end of file
+    expand(&input.to_string())
+}
@@ -0,0 +1,81 @@
+//! Helpers for build scripts
+
+use crate::error::Error;
+use crate::source::SourceMap;
+use crate::Generator;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Generates Rust code for a fl file into `OUT_DIR`, using the default options.
+/// See `compile_with`.
+pub fn compile<P: AsRef<Path>>(path: P) -> Result<PathBuf, Error> {
+    compile_with(&Generator::default(), path)
+}
+
+/// Generates Rust code for a fl file into `OUT_DIR/<file stem>.rs` and returns the generated path.
+/// Prints `cargo:rerun-if-changed` for the fl file and for the image and data files it references.
+/// The lint preamble is never emitted since the output is meant to be used with `include_generated!`.
+pub fn compile_with<P: AsRef<Path>>(generator: &Generator, path: P) -> Result<PathBuf, Error> {
+    let path = path.as_ref();
+    let out_dir = env::var_os("OUT_DIR").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "OUT_DIR is not set, fl2rust::build is meant to be used from build scripts",
+        )
+    })?;
+    println!("cargo:rerun-if-changed={}", path.display());
+    let mut name = path.file_stem().unwrap_or(path.as_os_str()).to_os_string();
+    name.push(".rs");
+    let outpath = Path::new(&out_dir).join(name);
+    let options = generator.options().clone().preamble(false);
+    let content = fs::read_to_string(path).map_err(Error::io(path))?;
+    generator.in_out_source(path, &content, &outpath, &options)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for file in SourceMap::new(&content).files() {
+        let relative = dir.join(file);
+        if relative.exists() {
+            println!("cargo:rerun-if-changed={}", relative.display());
+        } else {
+            println!("cargo:rerun-if-changed={}", file);
+        }
+    }
+    Ok(outpath)
+}
+
+/// Includes code generated by `fl2rust::build::compile` from `OUT_DIR`,
+/// allowing the lints generated code usually triggers.
+///
+/// `include_generated!(myui)` declares a `pub mod myui` with the contents of `OUT_DIR/myui.rs`,
+/// while `include_generated!(myui::*)` makes its items available in the current module,
+/// replacing a hand-written file with the lint preamble and an `include!`.
+/// The latter declares a private `myui` module, so several files can be included in the same module.
+#[macro_export]
+macro_rules! include_generated {
+    ($name:ident) => {
+        #[allow(
+            unused_variables,
+            unused_mut,
+            unused_imports,
+            dead_code,
+            clippy::needless_update
+        )]
+        pub mod $name {
+            include!(concat!(env!("OUT_DIR"), "/", stringify!($name), ".rs"));
+        }
+    };
+    ($name:ident::*) => {
+        #[allow(
+            unused_variables,
+            unused_mut,
+            unused_imports,
+            dead_code,
+            clippy::needless_update
+        )]
+        mod $name {
+            include!(concat!(env!("OUT_DIR"), "/", stringify!($name), ".rs"));
+        }
+        pub use self::$name::*;
+    };
+}
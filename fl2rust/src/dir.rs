@@ -0,0 +1,95 @@
+//! Generation of a whole directory of fl files
+
+use crate::error::Error;
+use crate::gen::ALLOWS;
+use crate::utils;
+use glob::{MatchOptions, Pattern};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Finds the files of `dir` whose path relative to `dir` matches one of the glob patterns.
+/// `*` doesn't match path separators, so `*.fl` only matches files at the top of `dir` while `**/*.fl` recurses.
+pub(crate) fn find_files(dir: &Path, patterns: &[&str]) -> Result<Vec<PathBuf>, Error> {
+    let patterns = patterns
+        .iter()
+        .map(|p| Pattern::new(p).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(d) = dirs.pop() {
+        for entry in fs::read_dir(&d).map_err(Error::io(&d))? {
+            let path = entry.map_err(Error::io(&d))?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Ok(rel) = path.strip_prefix(dir) {
+                if patterns.iter().any(|p| p.matches_path_with(rel, options)) {
+                    files.push(path);
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Module name of a fl file, from its path relative to the source directory
+pub(crate) fn module_name(rel: &Path) -> Result<String, Error> {
+    let mut name = String::new();
+    for part in rel.with_extension("").iter() {
+        if !name.is_empty() {
+            name.push('_');
+        }
+        name += &utils::to_snake_case(&part.to_string_lossy());
+    }
+    if utils::is_valid_ident(&name) {
+        Ok(name)
+    } else {
+        Err(Error::InvalidIdentifier {
+            location: None,
+            widget_path: rel.display().to_string(),
+            ident: name,
+        })
+    }
+}
+
+/// Module names of the fl files found in `dir`, which must be distinct
+pub(crate) fn module_names(dir: &Path, files: &[PathBuf]) -> Result<Vec<String>, Error> {
+    let mut modules: Vec<String> = vec![];
+    for (i, file) in files.iter().enumerate() {
+        let module = module_name(file.strip_prefix(dir).unwrap_or(file))?;
+        if let Some(j) = modules.iter().position(|m| *m == module) {
+            return Err(Error::DuplicateModule {
+                module,
+                files: [files[j].clone(), files[i].clone()],
+            });
+        }
+        modules.push(module);
+    }
+    Ok(modules)
+}
+
+/// Contents of the `mod.rs` declaring a module per generated file
+pub(crate) fn mod_rs(modules: &[String]) -> String {
+    let lints: Vec<_> = ALLOWS
+        .lines()
+        .filter_map(|l| l.strip_prefix("#![allow(")?.strip_suffix(")]"))
+        .collect();
+    let mut s = String::from("// Automatically generated by fl2rust\n");
+    for m in modules {
+        write!(
+            s,
+            "\n#[allow({})]\npub mod {} {{\n    include!(\"{}.rs\");\n}}\n",
+            lints.join(", "),
+            m,
+            m
+        )
+        .unwrap();
+    }
+    s
+}
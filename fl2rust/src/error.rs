@@ -0,0 +1,265 @@
+use fluid_parser::error::FluidError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Location of an item in a fl file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// The fl file, if generating from a file
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}:{}", file.display(), self.line, self.col),
+            None => write!(f, "{}:{}", self.line, self.col),
+        }
+    }
+}
+
+/// Errors raised while generating Rust code from a fl file
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the fl file or writing the output failed, on `path` when the error concerns a file
+    Io {
+        path: Option<PathBuf>,
+        error: io::Error,
+    },
+    /// The fl file couldn't be parsed
+    Parse { location: Location, message: String },
+    /// A widget type which has no Rust equivalent
+    UnsupportedWidget {
+        location: Option<Location>,
+        widget_path: String,
+        typ: String,
+    },
+    /// A property value which can't be translated
+    InvalidProperty {
+        location: Option<Location>,
+        widget_path: String,
+        property: String,
+        value: String,
+    },
+    /// A name which isn't a valid Rust identifier
+    InvalidIdentifier {
+        location: Option<Location>,
+        widget_path: String,
+        ident: String,
+    },
+    /// A code snippet, such as a callback or a declaration, which isn't valid Rust
+    InvalidCode {
+        location: Option<Location>,
+        widget_path: String,
+        property: String,
+        /// Why the snippet was rejected, quoting it
+        message: String,
+    },
+    /// Two fl files of a directory whose generated modules would have the same name
+    DuplicateModule { module: String, files: [PathBuf; 2] },
+}
+
+impl Error {
+    /// Gets the location of the error in the fl file, if known
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Io { .. } | Error::DuplicateModule { .. } => None,
+            Error::Parse { location, .. } => Some(location),
+            Error::UnsupportedWidget { location, .. }
+            | Error::InvalidProperty { location, .. }
+            | Error::InvalidIdentifier { location, .. }
+            | Error::InvalidCode { location, .. } => location.as_ref(),
+        }
+    }
+
+    /// Wraps an io error on `path`, for `map_err`
+    pub(crate) fn io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |error| Error::Io {
+            path: Some(path.to_path_buf()),
+            error,
+        }
+    }
+
+    /// Sets the fl file the error originates from
+    pub(crate) fn with_file(mut self, path: &Path) -> Self {
+        match &mut self {
+            Error::Io { .. } | Error::DuplicateModule { .. } => (),
+            Error::Parse { location, .. } => location.file = Some(path.to_path_buf()),
+            Error::UnsupportedWidget { location, .. }
+            | Error::InvalidProperty { location, .. }
+            | Error::InvalidIdentifier { location, .. }
+            | Error::InvalidCode { location, .. } => {
+                if let Some(location) = location {
+                    location.file = Some(path.to_path_buf());
+                }
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{}: ", location)?;
+        }
+        match self {
+            Error::Io {
+                path: Some(path),
+                error,
+            } => write!(f, "{}: {}", path.display(), error),
+            Error::Io { error, .. } => write!(f, "{}", error),
+            Error::Parse { message, .. } => write!(f, "{}", message),
+            Error::UnsupportedWidget {
+                widget_path, typ, ..
+            } => write!(f, "unsupported widget `{}` in {}", typ, widget_path),
+            Error::InvalidProperty {
+                widget_path,
+                property,
+                value,
+                ..
+            } => write!(
+                f,
+                "invalid value `{}` for property `{}` in {}",
+                value, property, widget_path
+            ),
+            Error::InvalidIdentifier {
+                widget_path, ident, ..
+            } => write!(
+                f,
+                "`{}` is not a valid identifier in {}",
+                ident, widget_path
+            ),
+            Error::InvalidCode {
+                widget_path,
+                property,
+                message,
+                ..
+            } => {
+                write!(f, "invalid code in property `{}`", property)?;
+                if !widget_path.is_empty() {
+                    write!(f, " of {}", widget_path)?;
+                }
+                write!(f, ": {}", message)
+            }
+            Error::DuplicateModule { module, files } => write!(
+                f,
+                "`{}` and `{}` would both be generated into module `{}`",
+                files[0].display(),
+                files[1].display(),
+                module
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Issues which don't stop generation, but make the output differ from the fl file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A property the widget's Rust type has no setter for, which was dropped
+    UnsupportedProperty {
+        location: Option<Location>,
+        widget_path: String,
+        property: String,
+        typ: String,
+    },
+    /// A font which isn't builtin, while no font lookup is set
+    UnknownFont {
+        location: Option<Location>,
+        widget_path: String,
+        property: String,
+        index: i32,
+    },
+}
+
+impl Warning {
+    /// Gets the location of the warning in the fl file, if known
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Warning::UnsupportedProperty { location, .. }
+            | Warning::UnknownFont { location, .. } => location.as_ref(),
+        }
+    }
+
+    /// Sets the fl file the warning originates from
+    pub(crate) fn with_file(mut self, path: &Path) -> Self {
+        match &mut self {
+            Warning::UnsupportedProperty { location, .. }
+            | Warning::UnknownFont { location, .. } => {
+                if let Some(location) = location {
+                    location.file = Some(path.to_path_buf());
+                }
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{}: ", location)?;
+        }
+        match self {
+            Warning::UnsupportedProperty {
+                widget_path,
+                property,
+                typ,
+                ..
+            } => write!(
+                f,
+                "property `{}` isn't supported by `{}`, dropped from {}",
+                property, typ, widget_path
+            ),
+            Warning::UnknownFont {
+                widget_path,
+                property,
+                index,
+                ..
+            } => write!(
+                f,
+                "font {} of property `{}` in {} isn't builtin, set a font lookup to map it",
+                index, property, widget_path
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io {
+            path: None,
+            error: e,
+        }
+    }
+}
+
+impl From<FluidError> for Error {
+    fn from(e: FluidError) -> Self {
+        let (loc, message) = match e {
+            FluidError::UnexpectedToken(loc) => (loc, "unexpected token".to_string()),
+            FluidError::UnexpectedEof(loc) => (loc, "unexpected end of file".to_string()),
+            FluidError::Parse(s, loc) => (loc, format!("failed to parse `{}`", s)),
+        };
+        Error::Parse {
+            location: Location {
+                file: None,
+                line: loc.line,
+                col: loc.col,
+            },
+            message,
+        }
+    }
+}
@@ -1,12 +1,18 @@
-use crate::utils;
-use fluid_parser::ast::*;
-use std::fmt::Write;
-use std::sync::atomic;
-use std::sync::Mutex;
+//! Generation of Rust code from the ast of a fl file.
+//! The ast lacks the properties fluid-parser doesn't keep, like grid tracks and window flags,
+//! which `Generator` reads back from the source. The functions of this module only see the ast
+//! and drop them, so they're deprecated in favour of `Generator`.
 
-static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
-static I18N: atomic::AtomicBool = atomic::AtomicBool::new(false);
-static LAST_MENU: Mutex<String> = Mutex::new(String::new());
+use crate::error::{Error, Location, Warning};
+use crate::mapper::{DefaultWidgetMapper, WidgetMapper, WidgetSpec};
+use crate::options::{GeneratorOptions, ImportStyle, WidgetNaming, WindowPosition};
+use crate::source::{Node, SourceMap};
+use crate::utils::{self, Directive};
+use crate::widgets::{Capabilities, WidgetCategory};
+use fluid_parser::ast::*;
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::quote;
+use std::collections::HashSet;
 
 pub const ALLOWS: &str = r#"// Automatically generated by fl2rust
 
@@ -16,206 +22,1236 @@ pub const ALLOWS: &str = r#"// Automatically generated by fl2rust
 #![allow(dead_code)]
 #![allow(clippy::needless_update)]"#;
 
-const HEADER: &str = r#"
-use fltk::browser::*;
-use fltk::button::*;
-use fltk::dialog::*;
-use fltk::enums::*;
-use fltk::frame::*;
-use fltk::group::*;
-use fltk::image::*;
-use fltk::input::*;
-use fltk::menu::*;
-use fltk::misc::*;
-use fltk::output::*;
-use fltk::prelude::*;
-use fltk::table::*;
-use fltk::text::*;
-use fltk::tree::*;
-use fltk::valuator::*;
-use fltk::widget::*;
-use fltk::window::*;"#;
-
-fn i18nize(s: &str) -> String {
-    if I18N.load(atomic::Ordering::Relaxed) {
-        format!("&tr!(\"{}\")", s)
+const MODULES: &[&str] = &[
+    "browser", "button", "dialog", "enums", "frame", "group", "image", "input", "menu", "misc",
+    "output", "prelude", "table", "text", "tree", "valuator", "widget", "window",
+];
+
+/// Name of the macro standing in for comments in the token stream,
+/// `__fl2rust_comment__!(N);` is replaced by the Nth comment when rendering to a string.
+const COMMENT_MARKER: &str = "__fl2rust_comment__";
+
+/// A comment of the string output
+struct LineComment {
+    text: String,
+    /// Whether the comment starts a new block, separated by a blank line when pretty printing
+    block: bool,
+}
+
+fn header(ctx: &Ctx) -> TokenStream {
+    let krate = &ctx.krate;
+    let modules = MODULES.iter().map(|m| Ident::new(m, Span::call_site()));
+    match ctx.opts.imports {
+        ImportStyle::Glob => quote! { #(use #krate::#modules::*;)* },
+        ImportStyle::Grouped => quote! { use #krate::{#(#modules::*),*}; },
+        ImportStyle::None => TokenStream::new(),
+    }
+}
+
+/// Integer literal, negative values are emitted as a negation
+fn int<T: Into<i64>>(v: T) -> TokenStream {
+    let v = v.into();
+    let lit = Literal::i64_unsuffixed(v.abs());
+    if v < 0 {
+        quote!(-#lit)
     } else {
-        format!("\"{}\"", s)
-    }
-}
-
-fn is_parent_type(typ: &str) -> bool {
-    matches!(
-        typ,
-        "Window"
-            | "Group"
-            | "Pack"
-            | "Tabs"
-            | "Scroll"
-            | "Table"
-            | "Tile"
-            | "Wizard"
-            | "MenuBar"
-            | "MenuButton"
-            | "Choice"
-            | "Flex"
-    )
+        quote!(#lit)
+    }
 }
 
-fn is_menu_type(typ: &str) -> bool {
-    matches!(typ, "MenuBar" | "SysMenuBar" | "MenuButton" | "Choice")
+/// Float literal, negative values are emitted as a negation
+fn float(v: f64) -> TokenStream {
+    let lit = Literal::f64_unsuffixed(v.abs());
+    if v < 0. {
+        quote!(-#lit)
+    } else {
+        quote!(#lit)
+    }
 }
 
-fn add_menus(widgets: &[Widget], sub: &mut Vec<String>) -> String {
-    let mut wid = String::new();
-    let mut substyle = String::new();
-    for w in widgets {
-        if w.typ == "MenuItem" {
-            wid += "\tlet idx = ";
-            {
-                wid += &*LAST_MENU.lock().unwrap();
-            }
-            wid += ".add_choice(";
-            let mut temp = String::new();
-            temp += &sub.iter().map(|x| x.to_owned() + "/").collect::<String>();
-            temp += w.props.label.as_ref().unwrap_or(&String::new());
-            wid += &i18nize(&temp);
-            wid += ");\n";
-
-            let name = &format!("{}.at(idx).unwrap()", *LAST_MENU.lock().unwrap());
-            if let Some(v) = &w.props.shortcut {
-                writeln!(
-                    wid,
-                    "\t{}.set_shortcut(unsafe {{std::mem::transmute({})}});",
-                    name, v
-                )
-                .unwrap();
-            }
-            if let Some(v) = &w.props.typ {
-                writeln!(wid, "\t{}.set_flag(MenuFlag::{});", name, v).unwrap();
-            } else if w.props.divider.is_some() {
-                writeln!(wid, "\t{}.set_flag(MenuFlag::MenuDivider);", name).unwrap();
-            }
-            if let Some(v) = &w.props.callback {
-                writeln!(wid, "\t{}.set_callback({});", name, v).unwrap();
-            }
-            if let Some(v) = &w.props.labeltype {
-                let temp = utils::global_to_pascal(v);
-                let temp = if temp == "No" { "None" } else { temp.as_str() };
-                writeln!(wid, "\t{}.set_label_type(LabelType::{});", name, temp).unwrap();
-            }
-            if let Some(v) = &w.props.labelfont {
-                writeln!(wid, "\t{}.set_label_font(Font::by_index({}));", name, v).unwrap();
+/// State of a single generation run.
+/// A fresh context is created for every call to `generate`, so no state leaks between files.
+struct Ctx<'a> {
+    /// Options of the current run
+    opts: &'a GeneratorOptions,
+    /// Mapping of fluid widget classes to Rust types
+    mapper: &'a dyn WidgetMapper,
+    /// Index of the fl source, used to locate errors
+    source: Option<&'a SourceMap>,
+    /// Position of the current node in the ast
+    pos: Vec<usize>,
+    /// Fluid type of the nodes along `pos`, checked against the source index
+    types: Vec<String>,
+    /// Human readable path of the current node
+    path: Vec<String>,
+    /// Names taken in the current function or struct
+    names: HashSet<String>,
+    /// Type and position of the current widget and its parents, used to name anonymous widgets
+    anonymous_path: Vec<String>,
+    /// Whether anonymous widgets of the current scope become struct fields
+    anonymous_fields: bool,
+    /// Whether strings should be wrapped in the `tr!` macro
+    i18n: bool,
+    /// Path of the fltk crate
+    krate: TokenStream,
+    /// Path of the i18n macro
+    i18n_macro: TokenStream,
+    /// Function mapping custom font indices to fonts
+    font_lookup: Option<TokenStream>,
+    /// Comments referenced by comment markers, `None` if comments are dropped
+    comments: Option<Vec<LineComment>>,
+    /// Name of the menu widget currently receiving menu items
+    last_menu: TokenStream,
+    /// Rows and columns of the grid whose children are being added
+    grid: Option<(i32, i32)>,
+    /// Fixed sizes by child index of the flex whose children are being added
+    fixed: Option<Vec<(usize, i32)>>,
+    /// Name of the innermost window around the current widget
+    window: Option<Ident>,
+    /// Dropped properties and other issues which don't stop generation
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Ctx<'a> {
+    fn new(
+        opts: &'a GeneratorOptions,
+        mapper: &'a dyn WidgetMapper,
+        source: Option<&'a SourceMap>,
+        ast: &Ast,
+        comments: bool,
+    ) -> Result<Self, Error> {
+        let mut ctx = Self {
+            opts,
+            mapper,
+            source,
+            pos: vec![],
+            types: vec![],
+            path: vec![],
+            names: HashSet::new(),
+            anonymous_path: vec![],
+            anonymous_fields: false,
+            i18n: ast.i18n_type.unwrap_or_default(),
+            krate: TokenStream::new(),
+            i18n_macro: TokenStream::new(),
+            font_lookup: None,
+            comments: if comments { Some(vec![]) } else { None },
+            last_menu: TokenStream::new(),
+            grid: None,
+            fixed: None,
+            window: None,
+            warnings: vec![],
+        };
+        ctx.krate = ctx.code("fltk_crate", &opts.fltk_crate)?;
+        ctx.i18n_macro = ctx.code("i18n_macro", &opts.i18n_macro)?;
+        if let Some(lookup) = &opts.font_lookup {
+            ctx.font_lookup = Some(ctx.code("font_lookup", lookup)?);
+        }
+        Ok(ctx)
+    }
+
+    /// Converts a string of the fl file to a literal, wrapped in the i18n macro if enabled
+    fn i18nize(&self, s: &str) -> TokenStream {
+        self.i18nize_text(&utils::fluid_string(s))
+    }
+
+    /// Same as `i18nize`, for text which was already unescaped
+    fn i18nize_text(&self, s: &str) -> TokenStream {
+        let lit = Literal::string(s);
+        if self.i18n {
+            let mac = &self.i18n_macro;
+            quote!(&#mac!(#lit))
+        } else {
+            quote!(#lit)
+        }
+    }
+
+    /// Emits a comment marker, rendered as a line comment in string output
+    fn comment(&mut self, text: &str, block: bool) -> TokenStream {
+        match &mut self.comments {
+            Some(comments) => {
+                let idx = Literal::usize_unsuffixed(comments.len());
+                comments.push(LineComment {
+                    text: text.to_string(),
+                    block,
+                });
+                let marker = Ident::new(COMMENT_MARKER, Span::call_site());
+                quote!(#marker!(#idx);)
+            }
+            None => TokenStream::new(),
+        }
+    }
+
+    fn enter(&mut self, idx: usize, typ: &str, segment: String) {
+        self.pos.push(idx);
+        self.types.push(typ.to_string());
+        self.path.push(segment);
+    }
+
+    fn leave(&mut self) {
+        self.pos.pop();
+        self.types.pop();
+        self.path.pop();
+    }
+
+    /// Gets the source node of the current ast node.
+    /// The source and the ast only differ where fluid-parser misread the file,
+    /// nodes whose type doesn't match along the way have no source node.
+    fn node(&self) -> Option<&'a Node> {
+        let source = self.source?;
+        let paired = (2..=self.pos.len()).all(|len| {
+            source
+                .node(&self.pos[..len])
+                .map_or(false, |n| n.typ == self.types[len - 1])
+        });
+        if paired {
+            source.node(&self.pos)
+        } else {
+            None
+        }
+    }
+
+    fn location(&self, prop: Option<&str>) -> Option<Location> {
+        let node = self.node()?;
+        let (line, col) = match prop.and_then(|p| node.prop(p).or_else(|| node.parent_prop(p))) {
+            Some(p) => (p.line, p.col),
+            None => (node.line, node.col),
+        };
+        Some(Location {
+            file: None,
+            line,
+            col,
+        })
+    }
+
+    /// A property fluid-parser doesn't keep, read from the source index
+    fn source_prop(&self, key: &str) -> Option<String> {
+        self.node()?.prop(key)?.value.clone()
+    }
+
+    /// Whether a flag fluid-parser doesn't keep is set, read from the source index
+    fn source_flag(&self, key: &str) -> bool {
+        self.node().map_or(false, |n| n.prop(key).is_some())
+    }
+
+    /// A property of the `parent_properties` block, read from the source index
+    fn parent_prop(&self, key: &str) -> Option<String> {
+        self.node()?.parent_prop(key)?.value.clone()
+    }
+
+    fn widget_path(&self) -> String {
+        self.path.join("/")
+    }
+
+    fn invalid_property(&self, property: &str, value: &str) -> Error {
+        Error::InvalidProperty {
+            location: self.location(Some(property)),
+            widget_path: self.widget_path(),
+            property: property.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Whether the widget supports the setters of a property, warning about it otherwise
+    fn supports(&mut self, spec: &WidgetSpec, capability: Capabilities, property: &str) -> bool {
+        if spec.capabilities.contains(capability) {
+            return true;
+        }
+        self.unsupported(&spec.path, property);
+        false
+    }
+
+    /// Warns about a comment directive the widget doesn't take, which is ignored
+    fn unsupported_directive(&mut self, typ: &str, key: &str) {
+        self.warnings.push(Warning::UnsupportedProperty {
+            location: self.location(Some("comment")),
+            widget_path: self.widget_path(),
+            property: key.to_string(),
+            typ: typ.to_string(),
+        });
+    }
+
+    /// Warns about a property dropped from a widget of type `typ`
+    fn unsupported(&mut self, typ: &str, property: &str) {
+        self.warnings.push(Warning::UnsupportedProperty {
+            location: self.location(Some(property)),
+            widget_path: self.widget_path(),
+            property: property.to_string(),
+            typ: typ.to_string(),
+        });
+    }
+
+    fn check_ident(&self, ident: &str) -> Result<(), Error> {
+        if utils::is_valid_ident(ident) {
+            Ok(())
+        } else {
+            Err(Error::InvalidIdentifier {
+                location: self.location(None),
+                widget_path: self.widget_path(),
+                ident: ident.to_string(),
+            })
+        }
+    }
+
+    /// Parses a user supplied code snippet, escaped as fluid stores it, e.g. `\#include`
+    fn code(&self, property: &str, code: &str) -> Result<TokenStream, Error> {
+        let code = utils::fluid_string(code);
+        code.parse()
+            .map_err(|e: proc_macro2::LexError| Error::InvalidCode {
+                location: self.location(Some(property)),
+                widget_path: self.widget_path(),
+                property: property.to_string(),
+                message: format!("{} in `{}`", e, code),
+            })
+    }
+
+    /// Converts a property value to an enum variant
+    fn variant(&self, property: &str, value: &str) -> Result<Ident, Error> {
+        if utils::is_valid_ident(value) {
+            Ok(Ident::new(value, Span::call_site()))
+        } else {
+            Err(self.invalid_property(property, value))
+        }
+    }
+
+    /// Gets the Rust spec of a widget, its `class` property overriding the type
+    fn spec(&self, w: &Widget) -> Result<WidgetSpec, Error> {
+        let mut spec = self
+            .mapper
+            .map(&w.typ)
+            .ok_or_else(|| Error::UnsupportedWidget {
+                location: self.location(None),
+                widget_path: self.widget_path(),
+                typ: w.typ.clone(),
+            })?;
+        if let Some(class) = &w.props.class {
+            spec.ctor = format!("{}::new", class);
+            spec.path = class.clone();
+        } else if self.opts.smart_tables && w.typ == "Fl_Table" && spec.path == "Table" {
+            spec.ctor = format!("{}::new", SMART_TABLE);
+            spec.path = SMART_TABLE.to_string();
+        }
+        for path in [&mut spec.path, &mut spec.ctor] {
+            if let Some(rest) = path.strip_prefix("fltk::") {
+                *path = format!("{}::{}", self.opts.fltk_crate, rest);
             }
-            if let Some(v) = &w.props.labelsize {
-                writeln!(wid, "\t{}.set_label_size({});", name, v).unwrap();
+        }
+        Ok(spec)
+    }
+
+    /// Starts a new naming scope, reserving the names given in the fl file
+    fn reset_names<'w>(&mut self, widgets: impl IntoIterator<Item = &'w Widget>) {
+        fn reserve(names: &mut HashSet<String>, w: &Widget) {
+            if !w.name.is_empty() {
+                names.insert(w.name.clone());
             }
-            if let Some(v) = &w.props.labelcolor {
-                writeln!(wid, "\t{}.set_label_color(Color::by_index({}));", name, v).unwrap();
+            for c in &w.children {
+                reserve(names, c);
             }
+        }
+        self.names.clear();
+        for w in widgets {
+            reserve(&mut self.names, w);
+        }
+    }
+
+    /// Names an anonymous widget, the name is unique within the current scope
+    fn anonymous_name(&mut self, w: &Widget) -> Ident {
+        let base = match self.opts.widget_naming {
+            WidgetNaming::Label => w.props.label.as_deref().and_then(utils::label_to_snake),
+            WidgetNaming::Path => None,
+        }
+        .unwrap_or_else(|| self.anonymous_path.join("_"));
+        self.unique_name(base)
+    }
+
+    /// Takes `base`, or `base_2`, `base_3`... if it's already taken in the current scope
+    fn unique_name(&mut self, base: String) -> Ident {
+        let mut name = base.clone();
+        let mut n = 2;
+        while !utils::is_valid_ident(&name) || self.names.contains(&name) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        self.names.insert(name.clone());
+        Ident::new(&name, Span::call_site())
+    }
+
+    /// Parses a whitespace separated list of integers
+    fn ints(&self, property: &str, value: &str, count: Option<usize>) -> Result<Vec<i32>, Error> {
+        let v = value
+            .split_ascii_whitespace()
+            .map(|e| e.parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| self.invalid_property(property, value))?;
+        if matches!(count, Some(c) if c != v.len()) {
+            return Err(self.invalid_property(property, value));
+        }
+        Ok(v)
+    }
+}
+
+/// Flags of `Align`, combined flags first
+const ALIGN_FLAGS: &[(i64, &str)] = &[
+    (0x1, "Top"),
+    (0x2, "Bottom"),
+    (0x4, "Left"),
+    (0x8, "Right"),
+    (0x10, "Inside"),
+    (0x120, "TextNextToImage"),
+    (0x100, "ImageNextToText"),
+    (0x20, "TextOverImage"),
+    (0x40, "Clip"),
+    (0x80, "Wrap"),
+    (0x200, "ImageBackdrop"),
+];
+
+/// Flags of `CallbackTrigger`
+const WHEN_FLAGS: &[(i64, &str)] = &[
+    (0x4, "Release"),
+    (0x8, "EnterKey"),
+    (0x1, "Changed"),
+    (0x2, "NotChanged"),
+    (0x10, "Closed"),
+];
+
+/// Modifier flags of `Shortcut`
+const SHORTCUT_FLAGS: &[(i64, &str)] = &[
+    (0x40000, "Ctrl"),
+    (0x80000, "Alt"),
+    (0x10000, "Shift"),
+    (0x400000, "Meta"),
+    (0x20000, "CapsLock"),
+];
+
+/// Named variants of `Key`
+const KEYS: &[(i64, &str)] = &[
+    (0xff08, "BackSpace"),
+    (0xff09, "Tab"),
+    (0xff0d, "Enter"),
+    (0xff13, "Pause"),
+    (0xff14, "ScrollLock"),
+    (0xff1b, "Escape"),
+    (0xff50, "Home"),
+    (0xff51, "Left"),
+    (0xff52, "Up"),
+    (0xff53, "Right"),
+    (0xff54, "Down"),
+    (0xff55, "PageUp"),
+    (0xff56, "PageDown"),
+    (0xff57, "End"),
+    (0xff61, "Print"),
+    (0xff63, "Insert"),
+    (0xff67, "Menu"),
+    (0xff68, "Help"),
+    (0xff7f, "NumLock"),
+    (0xff8d, "KPEnter"),
+    (0xffbe, "F1"),
+    (0xffbf, "F2"),
+    (0xffc0, "F3"),
+    (0xffc1, "F4"),
+    (0xffc2, "F5"),
+    (0xffc3, "F6"),
+    (0xffc4, "F7"),
+    (0xffc5, "F8"),
+    (0xffc6, "F9"),
+    (0xffc7, "F10"),
+    (0xffc8, "F11"),
+    (0xffc9, "F12"),
+    (0xffff, "Delete"),
+];
+
+/// Decodes a bitmask into an or-ed list of `enm` flags, `zero` naming the empty mask
+fn flags(
+    ctx: &Ctx,
+    property: &str,
+    enm: &str,
+    value: i64,
+    zero: &str,
+    table: &[(i64, &str)],
+) -> Result<Vec<TokenStream>, Error> {
+    let enm = Ident::new(enm, Span::call_site());
+    if value == 0 {
+        let zero = Ident::new(zero, Span::call_site());
+        return Ok(vec![quote!(#enm::#zero)]);
+    }
+    let mut rest = value;
+    let mut out = vec![];
+    for (bits, flag) in table {
+        if rest & bits == *bits {
+            rest &= !bits;
+            let flag = Ident::new(flag, Span::call_site());
+            out.push(quote!(#enm::#flag));
+        }
+    }
+    if rest != 0 {
+        return Err(ctx.invalid_property(property, &value.to_string()));
+    }
+    Ok(out)
+}
+
+fn align(ctx: &Ctx, v: i32) -> Result<TokenStream, Error> {
+    let v = flags(ctx, "align", "Align", v.into(), "Center", ALIGN_FLAGS)?;
+    Ok(quote!(#(#v)|*))
+}
+
+fn trigger(ctx: &Ctx, v: i32) -> Result<TokenStream, Error> {
+    let v = flags(
+        ctx,
+        "when",
+        "CallbackTrigger",
+        v.into(),
+        "Never",
+        WHEN_FLAGS,
+    )?;
+    Ok(quote!(#(#v)|*))
+}
+
+/// Decodes a shortcut, stored by fluid as the key or-ed with the modifiers,
+/// into e.g. `Shortcut::Ctrl | 's'`
+fn shortcut(ctx: &Ctx, v: &str) -> Result<TokenStream, Error> {
+    let value = match v.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => v.parse(),
+    }
+    .map_err(|_| ctx.invalid_property("shortcut", v))?;
+    let key = value & 0xffff;
+    let mods = flags(
+        ctx,
+        "shortcut",
+        "Shortcut",
+        value & !0xffff,
+        "None",
+        SHORTCUT_FLAGS,
+    )
+    .map_err(|_| ctx.invalid_property("shortcut", v))?;
+    if key == 0 {
+        return Ok(quote!(#(#mods)|*));
+    }
+    let key = match (u8::try_from(key), KEYS.iter().find(|k| k.0 == key)) {
+        (Ok(c @ 0x20..=0x7e), _) => {
+            let c = Literal::character(char::from(c));
+            quote!(#c)
+        }
+        (_, Some((_, name))) => {
+            let name = Ident::new(name, Span::call_site());
+            quote!(Key::#name)
+        }
+        _ => {
+            let lit = Literal::i64_unsuffixed(key);
+            quote!(Key::from_i32(#lit))
+        }
+    };
+    Ok(quote!(#(#mods)|* | #key))
+}
+
+/// Colors of the fltk colormap which have a name
+const COLORS: &[(u32, &str)] = &[
+    (0, "ForeGround"),
+    (7, "BackGround2"),
+    (8, "Inactive"),
+    (15, "Selection"),
+    (32, "Gray0"),
+    (39, "Dark3"),
+    (45, "Dark2"),
+    (47, "Dark1"),
+    (49, "BackGround"),
+    (50, "Light1"),
+    (52, "Light2"),
+    (54, "Light3"),
+    (56, "Black"),
+    (60, "DarkGreen"),
+    (63, "Green"),
+    (72, "DarkRed"),
+    (76, "DarkYellow"),
+    (88, "Red"),
+    (95, "Yellow"),
+    (136, "DarkBlue"),
+    (140, "DarkCyan"),
+    (152, "DarkMagenta"),
+    (216, "Blue"),
+    (223, "Cyan"),
+    (248, "Magenta"),
+    (255, "White"),
+];
+
+/// Translates a color, stored by fluid either as a colormap index or as 0xRRGGBB00
+fn color(ctx: &Ctx, v: u32) -> TokenStream {
+    if ctx.opts.raw_colors {
+        let lit = Literal::u32_unsuffixed(v);
+        return if v > 0xff {
+            quote!(Color::from_rgbi(#lit))
         } else {
-            sub.push(w.props.label.as_ref().unwrap_or(&String::new()).to_string());
-            let name = &format!(
-                "{}.find_item(\"{}\").unwrap()",
-                *LAST_MENU.lock().unwrap(),
-                {
-                    let mut s = sub.iter().map(|x| x.to_owned() + "/").collect::<String>();
-                    s.pop();
-                    s
-                }
-            );
-            if let Some(v) = &w.props.labeltype {
-                let temp = utils::global_to_pascal(v);
-                let temp = if temp == "No" { "None" } else { temp.as_str() };
-                writeln!(substyle, "\t{}.set_label_type(LabelType::{});", name, temp).unwrap();
-            }
-            if let Some(v) = &w.props.labelfont {
-                writeln!(
-                    substyle,
-                    "\t{}.set_label_font(Font::by_index({}));",
-                    name, v
-                )
-                .unwrap();
+            quote!(Color::by_index(#lit))
+        };
+    }
+    if v > 0xff {
+        let hex: Literal = format!("0x{:06x}", v >> 8).parse().expect("hex literal");
+        return quote!(Color::from_hex(#hex));
+    }
+    match COLORS.iter().find(|c| c.0 == v) {
+        Some((_, name)) => {
+            let name = Ident::new(name, Span::call_site());
+            quote!(Color::#name)
+        }
+        None => {
+            let v = Literal::u32_unsuffixed(v);
+            quote!(Color::by_index(#v))
+        }
+    }
+}
+
+/// The builtin fonts, by index
+const FONTS: &[&str] = &[
+    "Helvetica",
+    "HelveticaBold",
+    "HelveticaItalic",
+    "HelveticaBoldItalic",
+    "Courier",
+    "CourierBold",
+    "CourierItalic",
+    "CourierBoldItalic",
+    "Times",
+    "TimesBold",
+    "TimesItalic",
+    "TimesBoldItalic",
+    "Symbol",
+    "Screen",
+    "ScreenBold",
+    "Zapfdingbats",
+];
+
+/// Translates a font index, calling the font lookup for fonts loaded by the app
+fn font(ctx: &mut Ctx, property: &str, v: i32) -> Result<TokenStream, Error> {
+    let idx = usize::try_from(v).map_err(|_| ctx.invalid_property(property, &v.to_string()))?;
+    if let Some(name) = FONTS.get(idx) {
+        let name = Ident::new(name, Span::call_site());
+        return Ok(quote!(Font::#name));
+    }
+    let lit = Literal::i32_unsuffixed(v);
+    match &ctx.font_lookup {
+        Some(lookup) => Ok(quote!(#lookup(#lit))),
+        None => {
+            ctx.warnings.push(Warning::UnknownFont {
+                location: ctx.location(Some(property)),
+                widget_path: ctx.widget_path(),
+                property: property.to_string(),
+                index: v,
+            });
+            Ok(quote!(Font::by_index(#lit)))
+        }
+    }
+}
+
+fn widget_segment(w: &Widget) -> String {
+    if w.name.is_empty() {
+        w.typ.clone()
+    } else {
+        format!("{} {}", w.typ, w.name)
+    }
+}
+
+fn label_type(ctx: &Ctx, v: &str) -> Result<Ident, Error> {
+    let temp = utils::global_to_pascal(v);
+    let temp = if temp == "No" { "None" } else { temp.as_str() };
+    ctx.variant("labeltype", temp)
+}
+
+fn frame_type(ctx: &Ctx, property: &str, v: &str) -> Result<Ident, Error> {
+    let temp = utils::global_to_pascal(v);
+    let temp = match temp.as_str() {
+        "OflatBox" => "OFlatFrame",
+        "OshadowBox" => "OShadowBox",
+        "RflatBox" => "RFlatBox",
+        "RshadowBox" => "RShadowBox",
+        _ => temp.as_str(),
+    };
+    ctx.variant(property, temp)
+}
+
+/// Parses the `count index size...` tuples of the children of a flex with a fixed size
+fn fixed_sizes(ctx: &Ctx, sizes: &str, children: usize) -> Result<Vec<(usize, i32)>, Error> {
+    // fluid 1.4 renamed `set_size_tuples` to `fixed_size_tuples`
+    let property = match ctx.source_prop("set_size_tuples") {
+        Some(_) => "set_size_tuples",
+        None => "fixed_size_tuples",
+    };
+    let v = ctx.ints(property, sizes, None)?;
+    let (count, tuples) = v
+        .split_first()
+        .ok_or_else(|| ctx.invalid_property(property, sizes))?;
+    if usize::try_from(*count).ok() != Some(tuples.len() / 2) || tuples.len() % 2 != 0 {
+        return Err(ctx.invalid_property(property, sizes));
+    }
+    tuples
+        .chunks_exact(2)
+        .map(|e| match usize::try_from(e[0]) {
+            Ok(idx) if idx < children => Ok((idx, e[1])),
+            _ => Err(ctx.invalid_property(property, sizes)),
+        })
+        .collect()
+}
+
+/// Path of the table generated for `Fl_Table` with `GeneratorOptions::smart_tables`
+const SMART_TABLE: &str = "fltk_table::SmartTable";
+
+/// Creates the buffers of a text widget, as requested by the options and its directives
+fn text_buffers(
+    ctx: &mut Ctx,
+    spec: &WidgetSpec,
+    name: &Ident,
+    directives: &[Directive],
+    exposed: bool,
+    named: &mut Vec<(Ident, TokenStream)>,
+) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    let mut style = false;
+    for (key, value) in directives {
+        match key.as_str() {
+            "style_buffer" => style = directive_bool(ctx, key, value)?.to_string() == "true",
+            _ => ctx.unsupported_directive(&spec.path, key),
+        }
+    }
+    let mut buffers = vec![];
+    if ctx.opts.text_buffers {
+        let buffer = ctx.unique_name(format!("{}_buffer", name));
+        wid.extend(quote! {
+            let mut #buffer = TextBuffer::default();
+            #name.set_buffer(#buffer.clone());
+        });
+        buffers.push(buffer);
+    }
+    if style {
+        // attached by the app along with its style table, through `set_highlight_data`
+        let buffer = ctx.unique_name(format!("{}_style_buffer", name));
+        wid.extend(quote! { let mut #buffer = TextBuffer::default(); });
+        buffers.push(buffer);
+    }
+    if exposed {
+        named.extend(buffers.into_iter().map(|b| (b, quote!(TextBuffer))));
+    }
+    Ok(wid)
+}
+
+/// Error of a comment directive the widget doesn't accept
+fn invalid_directive(ctx: &Ctx, key: &str, value: &Option<String>) -> Error {
+    match value {
+        Some(value) => ctx.invalid_property("comment", &format!("{}={}", key, value)),
+        None => ctx.invalid_property("comment", key),
+    }
+}
+
+/// Reads a `key=N` directive
+fn directive_int(ctx: &Ctx, key: &str, value: &Option<String>) -> Result<i32, Error> {
+    value
+        .as_deref()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v >= 0)
+        .ok_or_else(|| invalid_directive(ctx, key, value))
+}
+
+/// Reads a `key` or `key=true|false` directive
+fn directive_bool(ctx: &Ctx, key: &str, value: &Option<String>) -> Result<TokenStream, Error> {
+    match value.as_deref() {
+        None | Some("true") => Ok(quote!(true)),
+        Some("false") => Ok(quote!(false)),
+        Some(_) => Err(invalid_directive(ctx, key, value)),
+    }
+}
+
+/// Reads a `key=variant` directive naming a variant of `enm`, e.g. `select=single`
+fn directive_variant(
+    ctx: &Ctx,
+    key: &str,
+    value: &Option<String>,
+    enm: &str,
+    variants: &[&str],
+) -> Result<TokenStream, Error> {
+    let v = value
+        .as_deref()
+        .and_then(|v| variants.iter().find(|e| e.eq_ignore_ascii_case(v)))
+        .ok_or_else(|| invalid_directive(ctx, key, value))?;
+    let enm = Ident::new(enm, Span::call_site());
+    let v = Ident::new(v, Span::call_site());
+    Ok(quote!(#enm::#v))
+}
+
+/// Rows and columns of a table, from its directives or the options
+fn table_size(ctx: &Ctx, directives: &[Directive]) -> Result<[(Ident, Option<i32>); 2], Error> {
+    let (mut rows, mut cols) = match ctx.opts.table_size {
+        Some((rows, cols)) => (Some(rows), Some(cols)),
+        None => (None, None),
+    };
+    for (key, value) in directives {
+        match key.as_str() {
+            "rows" => rows = Some(directive_int(ctx, key, value)?),
+            "cols" => cols = Some(directive_int(ctx, key, value)?),
+            _ => (),
+        }
+    }
+    Ok([
+        (Ident::new("rows", Span::call_site()), rows),
+        (Ident::new("cols", Span::call_site()), cols),
+    ])
+}
+
+/// Sizes a `SmartTable`, which sets up its cells from its options when created
+fn smart_table_opts(
+    ctx: &Ctx,
+    name: &Ident,
+    directives: &[Directive],
+) -> Result<TokenStream, Error> {
+    let mut opts = vec![];
+    for (field, v) in table_size(ctx, directives)? {
+        if let Some(v) = v {
+            let v = int(v);
+            opts.push(quote!(#field: #v));
+        }
+    }
+    if let Some((key, value)) = directives.iter().find(|(key, _)| key == "editable") {
+        let v = directive_bool(ctx, key, value)?;
+        opts.push(quote!(editable: #v));
+    }
+    Ok(quote! {
+        let mut #name = #name.with_opts(fltk_table::TableOpts {
+            #(#opts,)*
+            ..Default::default()
+        });
+    })
+}
+
+/// Configures a table from the directives of its comment
+fn table_settings(
+    ctx: &mut Ctx,
+    spec: &WidgetSpec,
+    name: &Ident,
+    directives: &[Directive],
+) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    let smart = spec.path == SMART_TABLE;
+    if !smart {
+        for (field, v) in table_size(ctx, directives)? {
+            if let Some(v) = v {
+                let setter = Ident::new(&format!("set_{}", field), Span::call_site());
+                let v = int(v);
+                wid.extend(quote! { #name.#setter(#v); });
             }
-            if let Some(v) = &w.props.labelsize {
-                writeln!(substyle, "\t{}.set_label_size({});", name, v).unwrap();
+        }
+    }
+    for (key, value) in directives {
+        match key.as_str() {
+            "rows" | "cols" => (),
+            "editable" if !smart => ctx.unsupported(&spec.path, "editable"),
+            "editable" => (),
+            "row_header" | "col_header" | "row_resize" | "col_resize" => {
+                let setter = Ident::new(&format!("set_{}", key), Span::call_site());
+                let v = directive_bool(ctx, key, value)?;
+                wid.extend(quote! { #name.#setter(#v); });
+            }
+            "row_height" | "col_width" => {
+                let setter = Ident::new(&format!("set_{}_all", key), Span::call_site());
+                let v = int(directive_int(ctx, key, value)?);
+                wid.extend(quote! { #name.#setter(#v); });
+            }
+            "select" if spec.type_enum.is_some() => {
+                let v = directive_variant(
+                    ctx,
+                    key,
+                    value,
+                    "TableRowSelectMode",
+                    &["None", "Single", "Multi"],
+                )?;
+                wid.extend(quote! { #name.set_type(#v); });
+            }
+            _ => ctx.unsupported_directive(&spec.path, key),
+        }
+    }
+    Ok(wid)
+}
+
+/// Configures a tree from the directives of its comment
+fn tree_settings(
+    ctx: &mut Ctx,
+    spec: &WidgetSpec,
+    name: &Ident,
+    directives: &[Directive],
+) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    for (key, value) in directives {
+        match key.as_str() {
+            "root_label" => {
+                let v = ctx.i18nize_text(value.as_deref().unwrap_or_default());
+                wid.extend(quote! { #name.set_root_label(#v); });
+            }
+            "show_root" | "show_collapse" => {
+                let setter = Ident::new(&format!("set_{}", key), Span::call_site());
+                let v = directive_bool(ctx, key, value)?;
+                wid.extend(quote! { #name.#setter(#v); });
+            }
+            "connector_style" => {
+                let v = directive_variant(
+                    ctx,
+                    key,
+                    value,
+                    "TreeConnectorStyle",
+                    &["None", "Dotted", "Solid"],
+                )?;
+                wid.extend(quote! { #name.set_connector_style(#v); });
+            }
+            "select" => {
+                let v = directive_variant(
+                    ctx,
+                    key,
+                    value,
+                    "TreeSelect",
+                    &["None", "Single", "Multi", "SingleDraggable"],
+                )?;
+                wid.extend(quote! { #name.set_select_mode(#v); });
+            }
+            _ => ctx.unsupported_directive(&spec.path, key),
+        }
+    }
+    Ok(wid)
+}
+
+/// Per row and column settings of grids, with the default fluid writes for untouched tracks
+const GRID_TRACKS: &[(&str, &str, i32)] = &[
+    ("rowheights", "set_row_height", 0),
+    ("rowweights", "set_row_weight", 50),
+    ("rowgaps", "set_row_gap", -1),
+    ("colwidths", "set_col_width", 0),
+    ("colweights", "set_col_weight", 50),
+    ("colgaps", "set_col_gap", -1),
+];
+
+/// `FL_GRID_FILL`, the default alignment of grid cells
+const GRID_FILL: i32 = 0x30;
+
+/// Flags of `GridAlign`, combined flags first
+const GRID_ALIGN_FLAGS: &[(i64, &str)] = &[
+    (0x30, "FILL"),
+    (0x5, "TOP_LEFT"),
+    (0x9, "TOP_RIGHT"),
+    (0x6, "BOTTOM_LEFT"),
+    (0xa, "BOTTOM_RIGHT"),
+    (0x1, "TOP"),
+    (0x2, "BOTTOM"),
+    (0x4, "LEFT"),
+    (0x8, "RIGHT"),
+    (0x10, "HORIZONTAL"),
+    (0x20, "VERTICAL"),
+    (0x40, "PROPORTIONAL"),
+];
+
+/// Configures the layout of a grid, returning its rows and columns
+fn grid_layout(
+    ctx: &mut Ctx,
+    name: &Ident,
+    w: &Widget,
+) -> Result<(TokenStream, (i32, i32)), Error> {
+    let mut wid = TokenStream::new();
+    let (rows, cols) = match &w.props.dimensions {
+        Some(v) => match ctx.ints("dimensions", v, Some(2))?[..] {
+            [rows, cols] if rows >= 0 && cols >= 0 => (rows, cols),
+            _ => return Err(ctx.invalid_property("dimensions", v)),
+        },
+        None => (0, 0),
+    };
+    let (r, c) = (int(rows), int(cols));
+    wid.extend(quote! { #name.set_layout(#r, #c); });
+    if let Some(v) = &w.props.margin {
+        let v = ctx.ints("margin", v, Some(4))?.into_iter().map(int);
+        wid.extend(quote! { #name.set_margin(#(#v),*); });
+    }
+    if let Some(v) = &w.props.gap {
+        let v = ctx.ints("gap", v, Some(2))?.into_iter().map(int);
+        wid.extend(quote! { #name.set_gap(#(#v),*); });
+    }
+    for (property, setter, default) in GRID_TRACKS {
+        if let Some(v) = ctx.source_prop(property) {
+            let count = if property.starts_with("row") {
+                rows
+            } else {
+                cols
+            };
+            let v = ctx.ints(property, &v, Some(count as usize))?;
+            let setter = Ident::new(setter, Span::call_site());
+            for (i, v) in v.into_iter().enumerate().filter(|(_, v)| v != default) {
+                let (i, v) = (Literal::usize_unsuffixed(i), int(v));
+                wid.extend(quote! { #name.#setter(#i, #v); });
             }
-            if let Some(v) = &w.props.labelcolor {
-                writeln!(
-                    substyle,
-                    "\t{}.set_label_color(Color::by_index({}));",
-                    name, v
-                )
-                .unwrap();
+        }
+    }
+    Ok((wid, (rows, cols)))
+}
+
+/// Places a child of a grid in the cell of its `parent_properties`
+fn grid_cell(
+    ctx: &mut Ctx,
+    grid: &Ident,
+    child: &TokenStream,
+    location: &str,
+    (rows, cols): (i32, i32),
+) -> Result<TokenStream, Error> {
+    let span = |ctx: &Ctx, property: &str| match ctx.parent_prop(property) {
+        Some(v) => ctx.ints(property, &v, Some(1)).map(|v| v[0]),
+        None => Ok(1),
+    };
+    let rowspan = span(ctx, "rowspan")?;
+    let colspan = span(ctx, "colspan")?;
+    let (row, col) = match ctx.ints("location", location, Some(2))?[..] {
+        [row, col]
+            if row >= 0
+                && col >= 0
+                && rowspan > 0
+                && colspan > 0
+                && row + rowspan <= rows
+                && col + colspan <= cols =>
+        {
+            (row, col)
+        }
+        _ => return Err(ctx.invalid_property("location", location)),
+    };
+    let range = |start: i32, span: i32| {
+        let first = Literal::i32_unsuffixed(start);
+        if span == 1 {
+            quote!(#first)
+        } else {
+            let end = Literal::i32_unsuffixed(start + span);
+            quote!(#first..#end)
+        }
+    };
+    let (row, col) = (range(row, rowspan), range(col, colspan));
+    // fltk-rs has no setter for the minimum size of cells
+    if ctx.parent_prop("min_size").is_some() {
+        ctx.unsupported("GridCell", "min_size");
+    }
+    let align = match ctx.parent_prop("align") {
+        Some(v) => ctx.ints("align", &v, Some(1))?[0],
+        None => GRID_FILL,
+    };
+    if align == GRID_FILL {
+        Ok(quote! { #grid.set_widget(&mut #child, #row, #col).unwrap(); })
+    } else {
+        let v = flags(
+            ctx,
+            "align",
+            "GridAlign",
+            align.into(),
+            "CENTER",
+            GRID_ALIGN_FLAGS,
+        )?;
+        Ok(quote! { #grid.set_widget_ext(&mut #child, #row, #col, #(#v)|*).unwrap(); })
+    }
+}
+
+/// Loads an image property, panicking at runtime when the file is missing
+fn image(path: &str) -> TokenStream {
+    let path = utils::fluid_string(path);
+    let msg = Literal::string(&format!("Could not find image: {}", path));
+    let path = Literal::string(&path);
+    quote!(SharedImage::load(#path).expect(#msg))
+}
+
+/// The `code0` to `code3` and `extra_code` snippets of a widget
+fn extra_code(ctx: &mut Ctx, w: &Widget) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    for (property, code) in [
+        ("code0", &w.props.code0),
+        ("code1", &w.props.code1),
+        ("code2", &w.props.code2),
+        ("code3", &w.props.code3),
+        ("extra_code", &w.props.extra_code),
+    ] {
+        if let Some(v) = code {
+            wid.extend(ctx.code(property, v)?);
+        }
+    }
+    Ok(wid)
+}
+
+/// Label styling shared by widgets, menu items and submenus
+fn label_style(ctx: &mut Ctx, name: &TokenStream, w: &Widget) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    if let Some(v) = &w.props.labeltype {
+        let v = label_type(ctx, v)?;
+        wid.extend(quote! { #name.set_label_type(LabelType::#v); });
+    }
+    if let Some(v) = w.props.labelfont {
+        let v = font(ctx, "labelfont", v)?;
+        wid.extend(quote! { #name.set_label_font(#v); });
+    }
+    if let Some(v) = w.props.labelsize {
+        let v = int(v);
+        wid.extend(quote! { #name.set_label_size(#v); });
+    }
+    if let Some(v) = w.props.labelcolor {
+        let v = color(ctx, v);
+        wid.extend(quote! { #name.set_label_color(#v); });
+    }
+    Ok(wid)
+}
+
+fn add_menus(
+    ctx: &mut Ctx,
+    widgets: &[Widget],
+    sub: &mut Vec<String>,
+    named: &mut Vec<(Ident, TokenStream)>,
+) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    for (i, w) in widgets.iter().enumerate() {
+        ctx.enter(i, &w.typ, widget_segment(w));
+        let menu = ctx.last_menu.clone();
+        if let Some(comment) = &w.props.comment {
+            wid.extend(ctx.comment(&utils::fluid_string(comment), false));
+        }
+        let label = utils::fluid_string(w.props.label.as_deref().unwrap_or_default());
+        sub.push(utils::menu_path_segment(&label));
+        let path = ctx.i18nize_text(&sub.join("/"));
+        let is_item = w.typ == "MenuItem";
+        let mut flags = vec![];
+        if !is_item {
+            flags.push(quote!(MenuFlag::Submenu));
+        } else if let Some(v) = &w.props.typ {
+            let v = ctx.variant("type", v)?;
+            flags.push(quote!(MenuFlag::#v));
+        }
+        if w.props.divider.is_some() {
+            flags.push(quote!(MenuFlag::MenuDivider));
+        }
+        if flags.is_empty() {
+            flags.push(quote!(MenuFlag::Normal));
+        }
+        let shortcut = match &w.props.shortcut {
+            Some(v) => shortcut(ctx, v)?,
+            None => quote!(Shortcut::None),
+        };
+        // unlike `add_choice`, `add` doesn't split labels on `|` nor tabs
+        wid.extend(quote! {
+            let idx = #menu.add(#path, #shortcut, #(#flags)|*, |_| ());
+        });
+        let name = quote!(#menu.at(idx).unwrap());
+        if !w.name.is_empty() {
+            // adding items can reallocate the menu, the handle is taken once the menu is complete
+            ctx.check_ident(&w.name)?;
+            let item = Ident::new(&w.name, Span::call_site());
+            named.push((item.clone(), quote!(MenuItem)));
+            wid.extend(quote! { let #item = idx; });
+        }
+        if let Some(v) = w.props.value.as_ref().filter(|_| is_item) {
+            let v = v
+                .parse::<i32>()
+                .map_err(|_| ctx.invalid_property("value", v))?;
+            if v != 0 {
+                wid.extend(quote! { #name.set(); });
             }
         }
-        if !w.children.is_empty() {
-            wid += &add_menus(&w.children, sub);
+        if w.props.hide.is_some() {
+            wid.extend(quote! { #name.hide(); });
         }
-        if w.children.last().is_some() {
-            sub.pop();
+        if w.props.deactivate.is_some() {
+            wid.extend(quote! { #name.deactivate(); });
         }
+        if let Some(v) = &w.props.image {
+            let v = image(v);
+            wid.extend(quote! { #name.add_image(Some(#v), true); });
+        }
+        if let Some(v) = &w.props.callback {
+            let v = ctx.code("callback", v)?;
+            wid.extend(quote! { #name.set_callback(#v); });
+        }
+        wid.extend(label_style(ctx, &name, w)?);
+        // fltk menu items have neither tooltips nor ids
+        if w.props.tooltip.is_some() {
+            ctx.unsupported(&w.typ, "tooltip");
+        }
+        if w.props.user_data.is_some() {
+            ctx.unsupported(&w.typ, "user_data");
+        }
+        wid.extend(extra_code(ctx, w)?);
+        if !is_item {
+            wid.extend(add_menus(ctx, &w.children, sub, named)?);
+        }
+        sub.pop();
+        ctx.leave();
     }
-    wid += &substyle;
-    wid
+    Ok(wid)
 }
 
 fn add_widgets(
-    parent: Option<&str>,
+    ctx: &mut Ctx,
+    parent: Option<&Ident>,
     widgets: &[Widget],
-    named: &mut Vec<(String, String)>,
-) -> String {
-    let mut wid = String::new();
-    let mut flex = String::new();
-    for w in widgets {
-        let mut name = String::new();
-        let mut refname = String::new();
-        let typ = if let Some(class) = &w.props.class {
-            class.to_owned()
-        } else {
-            utils::de_fl(&w.typ)
-        };
-        if typ != "MenuItem" && typ != "Submenu" {
-            if let Some(comment) = &w.props.comment {
-                wid += "\t// ";
-                wid += comment;
-                wid += "\n";
-            }
-            wid += "\tlet mut ";
-            if w.name.is_empty() {
-                let val = COUNTER.load(atomic::Ordering::Relaxed);
-                name += "fl2rust_widget_";
-                name += &val.to_string();
-                COUNTER.store(val + 1, atomic::Ordering::Relaxed);
+    named: &mut Vec<(Ident, TokenStream)>,
+) -> Result<TokenStream, Error> {
+    let mut wid = TokenStream::new();
+    let mut cells = TokenStream::new();
+    for (i, w) in widgets.iter().enumerate() {
+        ctx.enter(i, &w.typ, widget_segment(w));
+        if w.typ != "MenuItem" && w.typ != "Submenu" {
+            let spec = ctx.spec(w)?;
+            let typ = &spec.path;
+            if ctx.opts.pretty {
+                wid.extend(ctx.comment(&widget_segment(w), true));
+            }
+            let (comment, directives) = match &w.props.comment {
+                Some(comment) => utils::split_directives(&utils::fluid_string(comment)),
+                None => Default::default(),
+            };
+            if !comment.is_empty() {
+                wid.extend(ctx.comment(&comment, false));
+            }
+            let ty = ctx.code("class", typ)?;
+            let ctor = ctx.code("class", &spec.ctor)?;
+            ctx.anonymous_path.push(format!(
+                "{}{}",
+                utils::de_fl(&w.typ).to_ascii_lowercase(),
+                i
+            ));
+            let name = if w.name.is_empty() {
+                let name = ctx.anonymous_name(w);
+                if ctx.anonymous_fields {
+                    named.push((name.clone(), ty.clone()));
+                }
+                name
             } else {
-                name += &w.name;
-                named.push((name.clone(), typ.clone()));
-            }
-            if w.props.class.is_some() {
-                refname += "*";
-                refname += &name;
+                ctx.check_ident(&w.name)?;
+                let name = Ident::new(&w.name, Span::call_site());
+                named.push((name.clone(), ty.clone()));
+                name
+            };
+            let refname = if w.props.class.is_some() {
+                quote!(*#name)
             } else {
-                refname = name.clone();
-            }
-            wid += &name;
-            wid += " = ";
-            wid += &typ;
-            wid += "::new(";
-            for coord in w.props.xywh.split_ascii_whitespace() {
-                wid += coord;
-                wid += ", ";
+                quote!(#name)
+            };
+            let xywh = ctx
+                .ints("xywh", &w.props.xywh, Some(4))?
+                .into_iter()
+                .map(int);
+            wid.extend(quote! { let mut #name = #ctor(#(#xywh,)* None); });
+            if spec.path == SMART_TABLE {
+                wid.extend(smart_table_opts(ctx, &name, &directives)?);
+            }
+            let is_window = spec.capabilities.contains(Capabilities::WINDOW);
+            let is_tree = spec.capabilities.contains(Capabilities::TREE);
+            if is_window && ctx.window.is_none() {
+                match ctx.opts.window_position {
+                    WindowPosition::Designer => (),
+                    WindowPosition::Centered => {
+                        wid.extend(quote! { let mut #name = #name.center_screen(); });
+                    }
+                    WindowPosition::WindowManager => {
+                        wid.extend(quote! { #name.free_position(); });
+                    }
+                }
             }
-            wid += "None);\n";
             if let Some(label) = &w.props.label {
-                wid += "\t";
-                wid += &name;
-                wid += ".set_label(";
-                wid += &i18nize(label);
-                wid += ");\n";
+                let label = ctx.i18nize(label);
+                wid.extend(quote! { #name.set_label(#label); });
             }
 
             if let Some(v) = &w.props.typ {
-                let v = if typ == "Flex" {
+                let v = if w.typ == "Fl_Flex" {
                     if v == "HORIZONTAL" {
                         "Row"
                     } else {
@@ -224,469 +1260,709 @@ fn add_widgets(
                 } else {
                     v
                 };
-                writeln!(
-                    wid,
-                    "\t{}.set_type({}Type::{});",
-                    name,
-                    utils::fix_type(&typ),
-                    utils::global_to_pascal(v)
-                )
-                .unwrap();
-            } else if typ == "Flex" {
-                writeln!(wid, "\t{}.set_type(FlexType::Column);", name,).unwrap();
-            }
-            if let Some(v) = &w.props.align {
-                writeln!(
-                    wid,
-                    "\t{}.set_align(unsafe {{std::mem::transmute({})}});",
-                    name, v
-                )
-                .unwrap();
+                // widgets without a type enum, like groups, have no fltk-rs types to set
+                if let Some(enm) = &spec.type_enum {
+                    let enm = ctx.code("type", enm)?;
+                    let v = ctx.variant("type", &utils::global_to_pascal(v))?;
+                    wid.extend(quote! { #name.set_type(#enm::#v); });
+                } else {
+                    ctx.unsupported(&spec.path, "type");
+                }
+            } else if w.typ == "Fl_Flex" {
+                wid.extend(quote! { #name.set_type(FlexType::Column); });
+            }
+            if let Some(v) = w.props.align {
+                let v = align(ctx, v)?;
+                wid.extend(quote! { #name.set_align(#v); });
             }
             if w.props.resizable.is_some() {
-                if parent.is_none() {
-                    writeln!(wid, "\t{}.make_resizable(true);", name).unwrap();
+                if let Some(parent) = parent {
+                    wid.extend(quote! { #parent.resizable(&#refname); });
+                } else if matches!(
+                    spec.category,
+                    WidgetCategory::Group | WidgetCategory::Window
+                ) {
+                    // a top-level group resizes with whatever it's later added to
+                    wid.extend(quote! { #name.make_resizable(true); });
                 } else {
-                    writeln!(wid, "\t{}.resizable(&{});", parent.unwrap(), refname).unwrap();
+                    ctx.unsupported(&spec.path, "resizable");
                 }
             }
-            if w.props.modal.is_some() {
-                writeln!(wid, "\t{}.make_modal(true);", name).unwrap();
+            if w.props.modal.is_some() && ctx.supports(&spec, Capabilities::WINDOW, "modal") {
+                wid.extend(quote! { #name.make_modal(true); });
             }
-            if w.props.non_modal.is_some() {
-                writeln!(wid, "\t{}.make_modal(false);", name).unwrap();
+            if w.props.non_modal.is_some() && ctx.supports(&spec, Capabilities::WINDOW, "non_modal")
+            {
+                wid.extend(quote! { #name.make_modal(false); });
             }
             if w.props.hide.is_some() {
-                writeln!(wid, "\t{}.hide();", name).unwrap();
+                wid.extend(quote! { #name.hide(); });
             }
             if w.props.deactivate.is_some() {
-                writeln!(wid, "\t{}.deactivate();", name).unwrap();
+                wid.extend(quote! { #name.deactivate(); });
             }
-            if let Some(v) = &w.props.color {
-                writeln!(wid, "\t{}.set_color(Color::by_index({}));", name, v).unwrap();
+            if let Some(v) = w.props.color {
+                let v = color(ctx, v);
+                wid.extend(quote! { #name.set_color(#v); });
             }
-            if let Some(v) = &w.props.selection_color {
-                writeln!(
-                    wid,
-                    "\t{}.set_selection_color(Color::by_index({}));",
-                    name, v
-                )
-                .unwrap();
+            if let Some(v) = w.props.selection_color {
+                let v = color(ctx, v);
+                wid.extend(quote! { #name.set_selection_color(#v); });
             }
             if let Some(v) = &w.props.tooltip {
-                writeln!(wid, "\t{}.set_tooltip({});", name, i18nize(v)).unwrap();
+                let v = ctx.i18nize(v);
+                wid.extend(quote! { #name.set_tooltip(#v); });
+            }
+            if let Some(v) = w
+                .props
+                .xclass
+                .as_ref()
+                .filter(|_| ctx.supports(&spec, Capabilities::WINDOW, "xclass"))
+            {
+                let v = ctx.i18nize(v);
+                wid.extend(quote! { #name.set_xclass(#v); });
+            }
+            if w.props.noborder.is_some() && ctx.supports(&spec, Capabilities::WINDOW, "noborder") {
+                wid.extend(quote! { #name.set_border(false); });
+            }
+            if w.props.hotspot.is_some() {
+                // fluid sets the hotspot of the window around the widget, or of the window itself
+                match &ctx.window {
+                    Some(window) => wid.extend(quote! { #window.hotspot(&#refname); }),
+                    None if is_window => wid.extend(quote! { #name.hotspot(&#name.clone()); }),
+                    None => ctx.unsupported(&spec.path, "hotspot"),
+                }
             }
-            if let Some(v) = &w.props.xclass {
-                writeln!(wid, "\t{}.set_xclass({});", name, i18nize(v)).unwrap();
+            if ctx.source_flag("override") && ctx.supports(&spec, Capabilities::WINDOW, "override")
+            {
+                wid.extend(quote! { #name.set_override(); });
             }
-            if w.props.noborder.is_some() {
-                writeln!(wid, "\t{}.set_border(false);", name).unwrap();
+            if ctx.source_flag("fullscreen")
+                && ctx.supports(&spec, Capabilities::WINDOW, "fullscreen")
+            {
+                wid.extend(quote! { #name.fullscreen(true); });
             }
             if let Some(v) = &w.props.image {
-                writeln!(wid, "\t{0}.set_image(Some(SharedImage::load(\"{1}\").expect(\"Could not find image: {1}\")));", name, v).unwrap();
+                // the image of a window is its icon
+                let v = image(v);
+                if is_window {
+                    wid.extend(quote! { #name.set_icon(Some(#v)); });
+                } else {
+                    wid.extend(quote! { #name.set_image(Some(#v)); });
+                }
             }
             if let Some(v) = &w.props.deimage {
-                writeln!(wid, "\t{0}.set_deimage(Some(SharedImage::load(\"{1}\").expect(\"Could not find image: {1}\")));", name, v).unwrap();
+                let v = image(v);
+                wid.extend(quote! { #name.set_deimage(Some(#v)); });
             }
             if let Some(v) = &w.props.r#box {
-                let temp = utils::global_to_pascal(v);
-                let temp = match temp.as_str() {
-                    "OflatBox" => "OFlatFrame",
-                    "OshadowBox" => "OShadowBox",
-                    "RflatBox" => "RFlatBox",
-                    "RshadowBox" => "RShadowBox",
-                    _ => temp.as_str(),
-                };
-                writeln!(wid, "\t{}.set_frame(FrameType::{});", name, temp).unwrap();
-            }
-            if let Some(v) = &w.props.down_box {
-                let temp = utils::global_to_pascal(v);
-                let temp = match temp.as_str() {
-                    "OflatBox" => "OFlatFrame",
-                    "OshadowBox" => "OShadowBox",
-                    "RflatBox" => "RFlatBox",
-                    "RshadowBox" => "RShadowBox",
-                    _ => temp.as_str(),
-                };
-                writeln!(wid, "\t{}.set_down_frame(FrameType::{});", name, temp).unwrap();
-            }
-            if let Some(v) = &w.props.labeltype {
-                let temp = utils::global_to_pascal(v);
-                let temp = if temp == "No" { "None" } else { temp.as_str() };
-                writeln!(wid, "\t{}.set_label_type(LabelType::{});", name, temp).unwrap();
-            }
-            if let Some(v) = &w.props.labelfont {
-                writeln!(wid, "\t{}.set_label_font(Font::by_index({}));", name, v).unwrap();
-            }
-            if let Some(v) = &w.props.labelsize {
-                writeln!(wid, "\t{}.set_label_size({});", name, v).unwrap();
-            }
-            if let Some(v) = &w.props.labelcolor {
-                writeln!(wid, "\t{}.set_label_color(Color::by_index({}));", name, v).unwrap();
-            }
-            if let Some(v) = &w.props.when {
-                writeln!(
-                    wid,
-                    "\t{}.set_trigger(unsafe {{std::mem::transmute({})}});",
-                    name, v
-                )
-                .unwrap();
-            }
-            if let Some(v) = &w.props.textfont {
-                writeln!(wid, "\t{}.set_text_font(Font::by_index({}));", name, v).unwrap();
-            }
-            if let Some(v) = &w.props.textsize {
-                writeln!(wid, "\t{}.set_text_size({});", name, v).unwrap();
+                let v = frame_type(ctx, "box", v)?;
+                wid.extend(quote! { #name.set_frame(FrameType::#v); });
+            }
+            if let Some(v) = w
+                .props
+                .down_box
+                .as_ref()
+                .filter(|_| ctx.supports(&spec, Capabilities::DOWN_FRAME, "down_box"))
+            {
+                let v = frame_type(ctx, "down_box", v)?;
+                wid.extend(quote! { #name.set_down_frame(FrameType::#v); });
+            }
+            wid.extend(label_style(ctx, &quote!(#name), w)?);
+            if let Some(v) = w.props.when {
+                let v = trigger(ctx, v)?;
+                wid.extend(quote! { #name.set_trigger(#v); });
+            }
+            if let Some(v) = w
+                .props
+                .textfont
+                .filter(|_| is_tree || ctx.supports(&spec, Capabilities::TEXT, "textfont"))
+            {
+                let v = font(ctx, "textfont", v)?;
+                if is_tree {
+                    wid.extend(quote! { #name.set_item_label_font(#v); });
+                } else {
+                    wid.extend(quote! { #name.set_text_font(#v); });
+                }
             }
-            if let Some(v) = &w.props.textcolor {
-                writeln!(wid, "\t{}.set_text_color(Color::by_index({}));", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .textsize
+                .filter(|_| is_tree || ctx.supports(&spec, Capabilities::TEXT, "textsize"))
+            {
+                let v = int(v);
+                if is_tree {
+                    wid.extend(quote! { #name.set_item_label_size(#v); });
+                } else {
+                    wid.extend(quote! { #name.set_text_size(#v); });
+                }
             }
-            if let Some(v) = &w.props.shortcut {
-                writeln!(
-                    wid,
-                    "\t{}.set_shortcut(unsafe {{std::mem::transmute({})}});",
-                    name, v
-                )
-                .unwrap();
+            if let Some(v) = w
+                .props
+                .textcolor
+                .filter(|_| is_tree || ctx.supports(&spec, Capabilities::TEXT, "textcolor"))
+            {
+                let v = color(ctx, v);
+                if is_tree {
+                    wid.extend(quote! { #name.set_item_label_fgcolor(#v); });
+                } else {
+                    wid.extend(quote! { #name.set_text_color(#v); });
+                }
             }
-            if let Some(v) = &w.props.gap {
-                writeln!(wid, "\t{}.set_pad({});", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .shortcut
+                .as_ref()
+                .filter(|_| ctx.supports(&spec, Capabilities::SHORTCUT, "shortcut"))
+            {
+                let v = shortcut(ctx, v)?;
+                wid.extend(quote! { #name.set_shortcut(#v); });
+            }
+            if let Some(v) = w
+                .props
+                .gap
+                .as_ref()
+                .filter(|_| !spec.capabilities.contains(Capabilities::GRID))
+            {
+                if spec.capabilities.contains(Capabilities::PAD) {
+                    let v = int(ctx.ints("gap", v, Some(1))?[0]);
+                    wid.extend(quote! { #name.set_pad(#v); });
+                } else if ctx.supports(&spec, Capabilities::SPACING, "gap") {
+                    let v = int(ctx.ints("gap", v, Some(1))?[0]);
+                    wid.extend(quote! { #name.set_spacing(#v); });
+                }
             }
-            if let Some(v) = &w.props.minimum {
-                writeln!(wid, "\t{}.set_minimum({} as _);", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .minimum
+                .filter(|_| ctx.supports(&spec, Capabilities::RANGE, "minimum"))
+            {
+                let v = float(v);
+                wid.extend(quote! { #name.set_minimum(#v as _); });
             }
-            if let Some(v) = &w.props.maximum {
-                writeln!(wid, "\t{}.set_maximum({} as _);", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .maximum
+                .filter(|_| ctx.supports(&spec, Capabilities::RANGE, "maximum"))
+            {
+                let v = float(v);
+                wid.extend(quote! { #name.set_maximum(#v as _); });
             }
-            if let Some(v) = &w.props.size {
-                writeln!(wid, "\t{}.set_size({} as _);", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .size
+                .filter(|_| ctx.supports(&spec, Capabilities::SLIDER_SIZE, "size"))
+            {
+                let v = float(v);
+                wid.extend(quote! { #name.set_slider_size(#v as _); });
             }
-            if let Some(v) = &w.props.slider_size {
-                writeln!(wid, "\t{}.set_slider_size({} as _);", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .slider_size
+                .filter(|_| ctx.supports(&spec, Capabilities::SLIDER_SIZE, "slider_size"))
+            {
+                let v = float(v);
+                wid.extend(quote! { #name.set_slider_size(#v as _); });
             }
-            if let Some(v) = &w.props.step {
-                writeln!(wid, "\t{}.set_step({} as _, 1);", name, v).unwrap();
+            if let Some(v) = w
+                .props
+                .step
+                .filter(|_| ctx.supports(&spec, Capabilities::STEP, "step"))
+            {
+                let v = float(v);
+                wid.extend(quote! { #name.set_step(#v as _, 1); });
             }
             if let Some(v) = &w.props.user_data {
                 if let Some(stripped) = v.strip_prefix("id:") {
-                    writeln!(wid, "\t{}.set_id(\"{}\");", name, stripped).unwrap();
+                    let v = Literal::string(&utils::fluid_string(stripped));
+                    wid.extend(quote! { #name.set_id(#v); });
                 }
             }
-            if let Some(v) = &w.props.value {
-                let val = if typ.contains("Button") {
+            if let Some(v) = w
+                .props
+                .value
+                .as_ref()
+                .filter(|_| ctx.supports(&spec, Capabilities::VALUE, "value"))
+            {
+                let val = if spec.category == WidgetCategory::Button {
                     let b = v
                         .parse::<i32>()
-                        .expect("Buttons should have integral values");
+                        .map_err(|_| ctx.invalid_property("value", v))?;
                     if b != 0 {
-                        "true".to_string()
+                        quote!(true)
                     } else {
-                        "false".to_string()
+                        quote!(false)
                     }
-                } else if (typ.contains("Input") || typ.contains("Output"))
-                    && !typ.contains("Value")
-                {
-                    i18nize(v)
+                } else if spec.category == WidgetCategory::Input {
+                    ctx.i18nize(v)
                 } else {
-                    format!("{} as _", v)
+                    let v = ctx.code("value", v)?;
+                    quote!(#v as _)
                 };
-                writeln!(wid, "\t{}.set_value({});", name, val).unwrap();
+                wid.extend(quote! { #name.set_value(#val); });
             }
-            if let Some(v) = &w.props.code0 {
-                wid += "\t";
-                wid += v;
-                wid += "\n";
-            }
-            if let Some(v) = &w.props.code1 {
-                wid += "\t";
-                wid += v;
-                wid += "\n";
+            wid.extend(extra_code(ctx, w)?);
+            if let Some(v) = &w.props.callback {
+                let v = ctx.code("callback", v)?;
+                wid.extend(quote! { #name.set_callback(#v); });
             }
-            if let Some(v) = &w.props.code2 {
-                wid += "\t";
-                wid += v;
-                wid += "\n";
+
+            let mut fixed = None;
+            if let Some(sizes) = w
+                .props
+                .size_tuple
+                .as_ref()
+                .filter(|_| ctx.supports(&spec, Capabilities::FIXED, "fixed_size_tuples"))
+            {
+                fixed = Some(fixed_sizes(ctx, sizes, w.children.len())?);
             }
-            if let Some(v) = &w.props.code3 {
-                wid += "\t";
-                wid += v;
-                wid += "\n";
+            for (property, sizes) in [("margin", &w.props.margin), ("margins", &w.props.margins)] {
+                if let Some(sizes) = sizes
+                    .as_ref()
+                    .filter(|_| !spec.capabilities.contains(Capabilities::GRID))
+                    .filter(|_| ctx.supports(&spec, Capabilities::MARGINS, property))
+                {
+                    match ctx.ints(property, sizes, None)?[..] {
+                        [m] => {
+                            let m = int(m);
+                            wid.extend(quote! { #name.set_margin(#m); });
+                        }
+                        [l, t, r, b] => {
+                            let v = [l, t, r, b].into_iter().map(int);
+                            wid.extend(quote! { #name.set_margins(#(#v),*); });
+                        }
+                        _ => return Err(ctx.invalid_property(property, sizes)),
+                    }
+                }
             }
-            if let Some(v) = &w.props.extra_code {
-                wid += "\t";
-                wid += v;
-                wid += "\n";
+            if let Some(sizes) = w
+                .props
+                .size_range
+                .as_ref()
+                .filter(|_| ctx.supports(&spec, Capabilities::WINDOW, "size_range"))
+            {
+                let v = ctx.ints("size_range", sizes, None)?.into_iter().map(int);
+                wid.extend(quote! { #name.size_range(#(#v),*); });
             }
-            if let Some(v) = &w.props.callback {
-                writeln!(wid, "\t{}.set_callback({});", name, v).unwrap();
-            }
-
-            if let Some(sizes) = &w.props.size_tuple {
-                let count: Vec<_> = sizes.split_ascii_whitespace().collect();
-                let count: Vec<_> = count.iter().skip(1).collect();
-                for e in count.chunks_exact(2) {
-                    let idx: usize = e[0].parse().unwrap();
-                    writeln!(
-                        flex,
-                        "\t{0}.fixed(&{0}.child({1}).unwrap(), {2});",
-                        name, idx, e[1]
-                    )
-                    .unwrap();
-                }
-                writeln!(flex, "\t{}.recalc();", name).unwrap();
-            }
-            if let Some(sizes) = &w.props.margins {
-                let count: Vec<_> = sizes.split_ascii_whitespace().collect();
-                write!(wid, "\t{0}.set_margins(", name).unwrap();
-                for e in count {
-                    wid += e;
-                    wid += ", ";
+
+            if spec.capabilities.contains(Capabilities::TABLE) {
+                wid.extend(table_settings(ctx, &spec, &name, &directives)?);
+            } else if is_tree {
+                wid.extend(tree_settings(ctx, &spec, &name, &directives)?);
+            } else if spec.capabilities.contains(Capabilities::BUFFER) {
+                let exposed = !w.name.is_empty() || ctx.anonymous_fields;
+                wid.extend(text_buffers(
+                    ctx,
+                    &spec,
+                    &name,
+                    &directives,
+                    exposed,
+                    named,
+                )?);
+            } else {
+                for (key, _) in &directives {
+                    ctx.unsupported_directive(&spec.path, key);
                 }
-                wid += ");\n";
-            }
-            if let Some(sizes) = &w.props.size_range {
-                let count: Vec<_> = sizes.split_ascii_whitespace().collect();
-                write!(wid, "\t{0}.size_range(", name).unwrap();
-                for e in count {
-                    wid += e;
-                    wid += ", ";
+            }
+            let mut grid = None;
+            if spec.capabilities.contains(Capabilities::GRID) {
+                let (layout, dimensions) = grid_layout(ctx, &name, w)?;
+                wid.extend(layout);
+                grid = Some(dimensions);
+            } else if w.props.dimensions.is_some() {
+                ctx.unsupported(&spec.path, "dimensions");
+            }
+            if let Some(location) = w
+                .props
+                .parent_properties
+                .as_ref()
+                .and_then(|p| p.location.as_ref())
+            {
+                if let (Some(parent), Some(dimensions)) = (parent, ctx.grid) {
+                    cells.extend(grid_cell(ctx, parent, &refname, location, dimensions)?);
                 }
-                wid += ");\n";
             }
 
-            if is_menu_type(&typ) {
-                {
-                    *LAST_MENU.lock().unwrap() = name.to_string();
+            let outer_grid = std::mem::replace(&mut ctx.grid, grid);
+            let outer_fixed = std::mem::replace(&mut ctx.fixed, fixed);
+            let window = if is_window {
+                Some(name.clone())
+            } else {
+                ctx.window.clone()
+            };
+            let outer_window = std::mem::replace(&mut ctx.window, window);
+            let menu = if spec.category == WidgetCategory::Menu {
+                Some(quote!(#name))
+            } else if spec.capabilities.contains(Capabilities::MENU) {
+                Some(quote!(#name.menu_button()))
+            } else {
+                None
+            };
+            if let Some(menu) = menu {
+                ctx.last_menu = menu.clone();
+                let first = named.len();
+                wid.extend(add_menus(ctx, &w.children, &mut vec![], named)?);
+                for (item, _) in &named[first..] {
+                    wid.extend(quote! { let mut #item = #menu.at(#item).unwrap(); });
                 }
-                let ch = add_menus(&w.children, &mut vec![]);
-                wid += &ch;
             } else if !w.children.is_empty() {
-                let ch = add_widgets(Some(&name), &w.children, named);
-                wid += &ch;
+                wid.extend(add_widgets(ctx, Some(&name), &w.children, named)?);
+            }
+            ctx.grid = outer_grid;
+            ctx.window = outer_window;
+            let fixed = std::mem::replace(&mut ctx.fixed, outer_fixed);
+            if spec.category.is_group() {
+                wid.extend(quote! { #name.end(); });
             }
-            if is_parent_type(&typ) {
-                wid += "\t";
-                wid += &name;
-                wid += ".end();\n";
+            if fixed.map_or(false, |f| !f.is_empty()) {
+                wid.extend(quote! { #name.recalc(); });
             }
             if w.props.visible.is_some() {
-                writeln!(wid, "\t{}.show();", name).unwrap();
+                wid.extend(quote! { #name.show(); });
             }
+            if let (Some(parent), Some(fixed)) = (parent, &ctx.fixed) {
+                if let Some((_, size)) = fixed.iter().find(|(idx, _)| *idx == i) {
+                    let size = int(*size);
+                    wid.extend(quote! { #parent.fixed(&#refname, #size); });
+                }
+            }
+            ctx.anonymous_path.pop();
         }
+        ctx.leave();
     }
-    wid += &flex;
-    wid
+    wid.extend(cells);
+    Ok(wid)
 }
 
-fn add_funcs(functions: &[Function], free: bool, named: &mut Vec<(String, String)>) -> String {
-    let mut func = String::new();
-    for c in functions {
-        func += "\n    pub fn ";
-        func += &c.name;
-        if let Some(ret) = &c.props.return_type {
-            func += " -> ";
-            func += ret;
-        } else if !free && !c.name.contains("self") {
-            func += " -> Self";
-        }
-        func += " {\n";
-        if let Some(code) = &c.code {
-            func += "\t";
-            func += code;
-            func += "\n";
-        }
-        if !c.widgets.is_empty() {
-            func += &add_widgets(None, &c.widgets, named);
+fn add_funcs(
+    ctx: &mut Ctx,
+    functions: &[Function],
+    class: Option<&str>,
+    named: &mut Vec<(Ident, TokenStream)>,
+) -> Result<TokenStream, Error> {
+    let free = class.is_none();
+    let mut func = TokenStream::new();
+    for (i, c) in functions.iter().enumerate() {
+        let fname = c.name.split('(').next().unwrap_or_default().trim();
+        match class {
+            Some(class) => ctx.enter(i, "Function", format!("{}::{}", class, fname)),
+            None => ctx.enter(i, "Function", fname.to_string()),
         }
-        if free && c.props.return_type.is_none() {
-            func += "\t(\n";
-        } else if !c.name.contains("self") && !free {
-            func += "\tSelf {\n";
-        }
-        if !named.is_empty() && named.len() > 1 {
-            for n in named.iter() {
-                func += "\t    ";
-                func += &n.0;
-                func += ",\n";
-            }
-        } else if !named.is_empty() && named.len() == 1 {
-            func += "\t    ";
-            func += &named[0].0;
-            func += "\n";
+        if free {
+            ctx.reset_names(&c.widgets);
         }
+        let method = c.name.contains("self");
+        let sig = ctx.code("name", &c.name)?;
+        let ret = if let Some(ret) = &c.props.return_type {
+            let ret = ctx.code("return_type", ret)?;
+            quote!(-> #ret)
+        } else if !free && !method {
+            quote!(-> Self)
+        } else {
+            TokenStream::new()
+        };
+        let code = match &c.code {
+            Some(code) => ctx.code("code", code)?,
+            None => TokenStream::new(),
+        };
+        let widgets = add_widgets(ctx, None, &c.widgets, named)?;
+        let names = named.iter().map(|n| &n.0);
+        let tail = if free && c.props.return_type.is_none() {
+            quote!((#(#names),*))
+        } else if !free && !method {
+            quote!(Self { #(#names),* })
+        } else if named.len() == 1 {
+            quote!(#(#names)*)
+        } else if named.len() > 1 {
+            quote!((#(#names),*))
+        } else {
+            TokenStream::new()
+        };
         if free {
             named.clear();
         }
-        if free && c.props.return_type.is_none() {
-            func += "\t)";
-        } else if !c.name.contains("self") && !free {
-            func += "\t}";
-        }
-        func += "\n    }";
+        func.extend(quote! {
+            pub fn #sig #ret {
+                #code
+                #widgets
+                #tail
+            }
+        });
+        ctx.leave();
     }
-    func
+    Ok(func)
 }
 
-fn add_widget_class_ctor(w: &Widget, named: &mut Vec<(String, String)>) -> String {
-    let mut wid = String::new();
-    wid += "\n    pub fn new<L: Into<Option<&'static str>>>(x: i32, y: i32, w: i32, h: i32, label: L) -> Self {\n";
-    wid += "\tlet mut base_group = Group::new(0, 0, ";
-    for coord in w.props.xywh.split_ascii_whitespace().skip(2) {
-        wid += coord;
-        wid += ", ";
-    }
-    wid += "label);\n";
-    let name = "base_group";
+fn add_widget_class_ctor(
+    ctx: &mut Ctx,
+    w: &Widget,
+    named: &mut Vec<(Ident, TokenStream)>,
+) -> Result<TokenStream, Error> {
+    let xywh = ctx.ints("xywh", &w.props.xywh, Some(4))?;
+    let (width, height) = (int(xywh[2]), int(xywh[3]));
+    let name = Ident::new("base_group", Span::call_site());
+    let mut props = TokenStream::new();
     if w.props.resizable.is_some() {
-        writeln!(wid, "\t{}.make_resizable(true);", name).unwrap();
+        props.extend(quote! { #name.make_resizable(true); });
     }
-    if let Some(v) = &w.props.labeltype {
-        let temp = utils::global_to_pascal(v);
-        let temp = if temp == "No" { "None" } else { temp.as_str() };
-        writeln!(wid, "\t{}.set_label_type(LabelType::{});", name, temp).unwrap();
+    props.extend(label_style(ctx, &quote!(#name), w)?);
+    if let Some(v) = w.props.color {
+        let v = color(ctx, v);
+        props.extend(quote! { #name.set_color(#v); });
     }
-    if let Some(v) = &w.props.labelfont {
-        writeln!(wid, "\t{}.set_label_font(Font::by_index({}));", name, v).unwrap();
-    }
-    if let Some(v) = &w.props.labelsize {
-        writeln!(wid, "\t{}.set_label_size({});", name, v).unwrap();
+    let children = add_widgets(ctx, Some(&name), &w.children, named)?;
+    let names = named.iter().map(|n| &n.0);
+    Ok(quote! {
+        pub fn new<L: Into<Option<&'static str>>>(x: i32, y: i32, w: i32, h: i32, label: L) -> Self {
+            let mut #name = Group::new(0, 0, #width, #height, label);
+            #props
+            #name.end();
+            #children
+            #name.resize(x, y, w, h);
+            Self {
+                #name,
+                #(#names,)*
+            }
+        }
+    })
+}
+
+/// Generate the output Rust tokens
+fn generate_(ctx: &mut Ctx, ast: &Ast) -> Result<TokenStream, Error> {
+    let mut s = header(ctx);
+    let vis = ctx.code("field_visibility", ctx.opts.field_visibility.as_str())?;
+    for decl in &ast.decls {
+        s.extend(ctx.code("decl", &decl.decl)?);
     }
-    if let Some(v) = &w.props.labelcolor {
-        writeln!(wid, "\t{}.set_label_color(Color::by_index({}));", name, v).unwrap();
+    for comment in &ast.comments {
+        s.extend(ctx.comment(&utils::fluid_string(&comment.comment), true));
     }
-    if let Some(v) = &w.props.color {
-        writeln!(wid, "\t{}.set_color(Color::by_index({}));", name, v).unwrap();
+    if !ast.functions.is_empty() {
+        let mut local_named = vec![];
+        ctx.pos = vec![0];
+        ctx.types = vec![String::new()];
+        ctx.anonymous_fields = false;
+        s.extend(add_funcs(ctx, &ast.functions, None, &mut local_named)?);
     }
-    wid += "\tbase_group.end();\n";
-    if !w.children.is_empty() {
-        wid += &add_widgets(Some(name), &w.children, named);
+    for (i, c) in ast.widget_classes.iter().enumerate() {
+        let mut named = vec![];
+        ctx.pos = vec![1, i];
+        ctx.types = vec![String::new(), "widget_class".to_string()];
+        ctx.path = vec![c.name.clone()];
+        ctx.check_ident(&c.name)?;
+        ctx.reset_names(&c.children);
+        ctx.names.insert("base_group".to_string());
+        ctx.anonymous_fields = ctx.opts.anonymous_fields;
+        let class = Ident::new(&c.name, Span::call_site());
+        let fns = add_widget_class_ctor(ctx, c, &mut named)?;
+        let (fields, types): (Vec<_>, Vec<_>) = named.into_iter().unzip();
+        let krate = &ctx.krate;
+        s.extend(quote! {
+            #[derive(Debug, Clone)]
+            pub struct #class {
+                #vis base_group: Group,
+                #(#vis #fields: #types,)*
+            }
+
+            impl #class {
+                #fns
+            }
+
+            #krate::widget_extends!(#class, Group, base_group);
+        });
     }
-    wid += "\tbase_group.resize(x, y, w, h);\n";
-    wid += "\tSelf {\n\t    base_group,\n";
-    if !named.is_empty() {
-        for n in named.iter() {
-            wid += "\t    ";
-            wid += &n.0;
-            wid += ",\n";
+    for (i, c) in ast.classes.iter().enumerate() {
+        let mut named = vec![];
+        ctx.pos = vec![2, i];
+        ctx.types = vec![String::new(), "class".to_string()];
+        ctx.path = vec![c.name.clone()];
+        ctx.check_ident(&c.name)?;
+        ctx.path.clear();
+        ctx.reset_names(c.functions.iter().flat_map(|f| &f.widgets));
+        ctx.anonymous_fields = ctx.opts.anonymous_fields;
+        let class = Ident::new(&c.name, Span::call_site());
+        let fns = add_funcs(ctx, &c.functions, Some(&c.name), &mut named)?;
+        let (fields, types): (Vec<_>, Vec<_>) = named.into_iter().unzip();
+        s.extend(quote! {
+            #[derive(Debug, Clone)]
+            pub struct #class {
+                #(#vis #fields: #types,)*
+            }
+        });
+        if !c.functions.is_empty() {
+            s.extend(quote! {
+                impl #class {
+                    #fns
+                }
+            });
         }
     }
-    wid += "\t}";
-    wid += "\n    }";
-    wid
+    Ok(s)
 }
 
-/// Generate the output Rust string/file
-fn generate_(ast: &Ast) -> String {
-    let mut s = String::new();
-    if let Some(i18n) = ast.i18n_type {
-        I18N.store(i18n, atomic::Ordering::Relaxed);
-    }
-    s += "\n";
-    let mut classes = vec![];
-    let mut widget_classes = vec![];
-    let mut funcs = vec![];
-    if !ast.decls.is_empty() {
-        for decl in &ast.decls {
-            s += &decl.decl;
-            s += "\n";
+/// Appends a comment as line comments with the passed indentation
+fn push_comment(out: &mut String, comment: &LineComment, indent: &str) {
+    for line in comment.text.lines() {
+        out.push_str(indent);
+        if !line.trim_start().starts_with("//") {
+            out.push_str("// ");
         }
-        s += "\n";
+        out.push_str(line.trim_start());
+        out.push('\n');
     }
-    if !ast.comments.is_empty() {
-        for comment in &ast.comments {
-            s += &comment.comment;
-            s += "\n";
+}
+
+/// Gets the comment referenced by a marker, `rest` starts right after the marker name.
+/// Returns the comment and the length of the marker arguments including the semicolon.
+fn marker_comment<'a>(rest: &str, comments: &'a [LineComment]) -> (Option<&'a LineComment>, usize) {
+    let end = rest.find(';').map(|e| e + 1).unwrap_or(rest.len());
+    let idx: String = rest[..end].chars().filter(char::is_ascii_digit).collect();
+    let comment = idx.parse::<usize>().ok().and_then(|i| comments.get(i));
+    (comment, end)
+}
+
+/// Renders tokens to a string, replacing comment markers with line comments
+fn render(tokens: &TokenStream, comments: &[LineComment]) -> String {
+    let code = tokens.to_string();
+    let mut out = String::new();
+    let mut rest = code.as_str();
+    while let Some(pos) = rest.find(COMMENT_MARKER) {
+        out += &rest[..pos];
+        rest = &rest[pos + COMMENT_MARKER.len()..];
+        let (comment, end) = marker_comment(rest, comments);
+        if let Some(comment) = comment {
+            out += "\n";
+            push_comment(&mut out, comment, "");
         }
+        rest = &rest[end..];
     }
-    if !ast.functions.is_empty() {
-        let mut local_named = vec![];
-        let func = add_funcs(&ast.functions, true, &mut local_named);
-        funcs.push(func);
-    }
-    if !ast.widget_classes.is_empty() {
-        let mut named: Vec<(String, String)> = vec![];
-        let mut class = String::new();
-        for c in &ast.widget_classes {
-            class += "#[derive(Debug, Clone)]\n";
-            class += "pub struct ";
-            class += &c.name;
-            class += " {\n";
-            class += "    pub base_group: Group,\n";
-            let fns = add_widget_class_ctor(c, &mut named);
-            if !named.is_empty() {
-                for n in &named {
-                    class += "    pub ";
-                    class += &n.0;
-                    class += ": ";
-                    class += &n.1;
-                    class += ",\n";
+    out += rest;
+    out
+}
+
+/// Whether `line` starts an item which should be separated from the previous line by a blank line
+fn starts_item(prev: &str, line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    if prev.trim() == "}" && prev.len() - 1 == indent {
+        indent == 0 || trimmed.starts_with("pub fn ") || trimmed.starts_with("fn ")
+    } else {
+        indent == 0 && prev.starts_with("use ") && !trimmed.starts_with("use ")
+    }
+}
+
+/// Renders tokens to a formatted string, replacing comment markers with line comments.
+/// Falls back to `render` if the tokens aren't a valid Rust file.
+fn render_pretty(tokens: &TokenStream, comments: &[LineComment]) -> String {
+    let file = match syn::parse2::<syn::File>(tokens.clone()) {
+        Ok(file) => file,
+        Err(_) => return render(tokens, comments),
+    };
+    let code = prettyplease::unparse(&file);
+    let mut out = String::new();
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        match trimmed.strip_prefix(COMMENT_MARKER) {
+            Some(rest) => {
+                if let (Some(comment), _) = marker_comment(rest, comments) {
+                    if comment.block
+                        && !out.is_empty()
+                        && !out.ends_with("{\n")
+                        && !out.ends_with("\n\n")
+                    {
+                        out += "\n";
+                    }
+                    push_comment(&mut out, comment, &line[..line.len() - trimmed.len()]);
                 }
             }
-            named.clear();
-            class += "}\n\n";
-            class += "impl ";
-            class += &c.name;
-            class += " {";
-            class += &fns;
-            class += "\n}\n\n";
-            class += "fltk::widget_extends!(";
-            class += &c.name;
-            class += ", Group, base_group);\n\n";
-        }
-        widget_classes.push(class);
-    }
-    if !ast.classes.is_empty() {
-        let mut named: Vec<(String, String)> = vec![];
-        let mut class = String::new();
-        for c in &ast.classes {
-            class += "#[derive(Debug, Clone)]\n";
-            class += "pub struct ";
-            class += &c.name;
-            class += " {\n";
-            let fns = add_funcs(&c.functions, false, &mut named);
-            if !named.is_empty() {
-                for n in &named {
-                    class += "    pub ";
-                    class += &n.0;
-                    class += ": ";
-                    class += &n.1;
-                    class += ",\n";
+            None => {
+                if starts_item(out.lines().last().unwrap_or_default(), line) {
+                    out += "\n";
                 }
-            }
-            named.clear();
-            class += "}\n\n";
-            if !c.functions.is_empty() {
-                class += "impl ";
-                class += &c.name;
-                class += " {";
-                class += &fns;
-                class += "\n}\n\n";
+                out += line;
+                out += "\n";
             }
         }
-        classes.push(class);
     }
-    for f in funcs {
-        s += &f;
-        s += "\n";
-    }
-    for c in widget_classes {
-        s += &c;
-        s += "\n";
-    }
-    for c in classes {
-        s += &c;
-        s += "\n";
-    }
-    s
+    out
 }
 
 /// Generate the output Rust string/file
+/// # Panics
+/// Panics if the ast contains invalid properties, names or code.
+#[deprecated(
+    note = "panics on invalid fl files and drops properties missing from the ast, use `Generator::generate_str`"
+)]
 pub fn generate(ast: &Ast) -> String {
-    let s = generate_(ast);
-    format!("{}\n{}", HEADER, s)
+    generate_or_panic(ast, &GeneratorOptions::default())
 }
 
 /// Generate the output Rust string/file
+/// # Panics
+/// Panics if the ast contains invalid properties, names or code.
+#[deprecated(
+    note = "panics on invalid fl files and drops properties missing from the ast, use `Generator::generate_str` with `GeneratorOptions::preamble(true)`"
+)]
 pub fn generate_with_directives_preamble(ast: &Ast) -> String {
-    let s = generate_(ast);
-    format!("{}\n{}\n{}", ALLOWS, HEADER, s)
+    generate_or_panic(ast, &GeneratorOptions::default().preamble(true))
+}
+
+/// Generate the output Rust string/file using the passed options
+/// # Panics
+/// Panics if the ast contains invalid properties, names or code.
+#[deprecated(
+    note = "panics on invalid fl files and drops properties missing from the ast, use `Generator::generate_str`"
+)]
+pub fn generate_with_options(ast: &Ast, opts: &GeneratorOptions) -> String {
+    generate_or_panic(ast, opts)
+}
+
+fn generate_or_panic(ast: &Ast, opts: &GeneratorOptions) -> String {
+    try_generate(ast, None, opts, &DefaultWidgetMapper)
+        .map(|(out, _)| out)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Generate the output Rust tokens using the passed options.
+/// Comments are dropped and the preamble isn't emitted, since inner attributes can't be spliced into a module.
+#[deprecated(note = "drops properties missing from the ast, use `Generator::generate_tokens`")]
+pub fn generate_tokens(ast: &Ast, opts: &GeneratorOptions) -> Result<TokenStream, Error> {
+    try_generate_tokens(ast, None, opts, &DefaultWidgetMapper).map(|(tokens, _)| tokens)
+}
+
+/// Generate the output Rust tokens and the warnings raised on the way,
+/// locating errors with the source index if available
+pub(crate) fn try_generate_tokens(
+    ast: &Ast,
+    source: Option<&SourceMap>,
+    opts: &GeneratorOptions,
+    mapper: &dyn WidgetMapper,
+) -> Result<(TokenStream, Vec<Warning>), Error> {
+    let mut ctx = Ctx::new(opts, mapper, source, ast, false)?;
+    let tokens = generate_(&mut ctx, ast)?;
+    Ok((tokens, ctx.warnings))
+}
+
+/// Generate the output Rust string/file and the warnings raised on the way,
+/// locating errors with the source index if available
+pub(crate) fn try_generate(
+    ast: &Ast,
+    source: Option<&SourceMap>,
+    opts: &GeneratorOptions,
+    mapper: &dyn WidgetMapper,
+) -> Result<(String, Vec<Warning>), Error> {
+    let mut ctx = Ctx::new(opts, mapper, source, ast, true)?;
+    let tokens = generate_(&mut ctx, ast)?;
+    let comments = ctx.comments.as_deref().unwrap_or_default();
+    let s = if opts.pretty {
+        render_pretty(&tokens, comments)
+    } else {
+        render(&tokens, comments) + "\n"
+    };
+    let s = if opts.preamble {
+        format!("{}\n\n{}", ALLOWS, s)
+    } else {
+        s
+    };
+    Ok((s, ctx.warnings))
 }
@@ -1,61 +1,1065 @@
 #![doc = include_str!("../README.md")]
 #![allow(clippy::needless_doctest_main)]
 
+pub mod build;
+mod dir;
+mod error;
 pub mod gen;
+mod mapper;
+mod options;
+mod source;
+mod stamp;
 mod utils;
+mod widgets;
+
+pub use error::{Error, Location, Warning};
+pub use mapper::{DefaultWidgetMapper, WidgetMapper, WidgetSpec};
+pub use options::{FieldVisibility, GeneratorOptions, ImportStyle, WidgetNaming, WindowPosition};
+pub use widgets::{Capabilities, WidgetCategory};
 
 use fluid_parser::lexer::Lexer;
 use fluid_parser::parser::Parser;
-use std::error;
+use proc_macro2::TokenStream;
 use std::fs;
+use std::io::{Read, Write};
 use std::path::*;
 
 /// Generator struct
 #[derive(Default)]
-pub struct Generator {}
+pub struct Generator {
+    options: GeneratorOptions,
+    /// Custom widget mapping, `DefaultWidgetMapper` if unset
+    mapper: Option<Box<dyn WidgetMapper>>,
+}
 
 impl Generator {
+    /// Creates a generator using the passed options
+    pub fn with_options(options: GeneratorOptions) -> Self {
+        Self {
+            options,
+            mapper: None,
+        }
+    }
+
+    /// Sets the mapping of fluid widget classes to Rust types
+    pub fn widget_mapper<M: WidgetMapper + 'static>(mut self, mapper: M) -> Self {
+        self.mapper = Some(Box::new(mapper));
+        self
+    }
+
+    fn mapper(&self) -> &dyn WidgetMapper {
+        match &self.mapper {
+            Some(mapper) => mapper.as_ref(),
+            None => &DefaultWidgetMapper,
+        }
+    }
+
+    /// Gets the options used by the generator
+    pub fn options(&self) -> &GeneratorOptions {
+        &self.options
+    }
+
+    /// Takes an input and output files.
+    /// Warnings are printed as `cargo:warning` directives, for use in build scripts.
+    pub fn in_out<P: AsRef<Path>>(&self, inpath: P, outpath: P) -> Result<(), Error> {
+        self.in_out_(inpath.as_ref(), outpath.as_ref(), &self.options)
+    }
+
     /// Takes an input and output files
-    pub fn in_out<P: AsRef<Path>>(
+    pub fn in_out_with_directives_preamble<P: AsRef<Path>>(
         &self,
         inpath: P,
         outpath: P,
-    ) -> Result<(), Box<dyn error::Error>> {
-        let content = fs::read_to_string(inpath)?;
-        let lexer = Lexer::new(&content);
-        let mut parser = Parser::new(lexer);
-        fs::write(outpath, gen::generate(&parser.parse()))?;
+    ) -> Result<(), Error> {
+        let options = self.options.clone().preamble(true);
+        self.in_out_(inpath.as_ref(), outpath.as_ref(), &options)
+    }
+
+    /// Generates a Rust file for each fl file at the top of `src_dir` into `out_dir`,
+    /// along with a `mod.rs` declaring a module per file.
+    /// Prints `cargo:rerun-if-changed` for the directory and every input, for use in build scripts.
+    pub fn in_out_dir<P: AsRef<Path>>(&self, src_dir: P, out_dir: P) -> Result<(), Error> {
+        self.in_out_dir_matching(src_dir, out_dir, &["*.fl"])
+    }
+
+    /// Same as `in_out_dir`, but only for the files matching one of the glob patterns,
+    /// relative to `src_dir`, e.g. `**/*.fl` to include subdirectories
+    pub fn in_out_dir_matching<P: AsRef<Path>>(
+        &self,
+        src_dir: P,
+        out_dir: P,
+        patterns: &[&str],
+    ) -> Result<(), Error> {
+        let (src_dir, out_dir) = (src_dir.as_ref(), out_dir.as_ref());
+        println!("cargo:rerun-if-changed={}", src_dir.display());
+        let files = dir::find_files(src_dir, patterns)?;
+        // checked before generating anything, so no output is overwritten by another file's
+        let modules = dir::module_names(src_dir, &files)?;
+        fs::create_dir_all(out_dir).map_err(Error::io(out_dir))?;
+        for (file, module) in files.iter().zip(&modules) {
+            println!("cargo:rerun-if-changed={}", file.display());
+            let options = self.options.clone().preamble(false);
+            self.in_out_(file, &out_dir.join(format!("{}.rs", module)), &options)?;
+        }
+        let mod_rs = dir::mod_rs(&modules);
+        let mod_path = out_dir.join("mod.rs");
+        if self.options.force {
+            fs::write(&mod_path, mod_rs).map_err(Error::io(&mod_path))?;
+        } else {
+            stamp::write_if_changed(&mod_path, &mod_rs).map_err(Error::io(&mod_path))?;
+        }
         Ok(())
     }
 
-    /// Takes an input and output files
-    pub fn in_out_with_directives_preamble<P: AsRef<Path>>(
+    /// Generates Rust source from the contents of a fl file
+    pub fn generate_str(&self, source: &str) -> Result<String, Error> {
+        self.generate_source(source, &self.options)
+            .map(|(out, _)| out)
+    }
+
+    /// Generates Rust source from the contents of a fl file,
+    /// along with warnings about the properties which couldn't be translated
+    pub fn generate_with_warnings(&self, source: &str) -> Result<(String, Vec<Warning>), Error> {
+        self.generate_source(source, &self.options)
+    }
+
+    /// Generates Rust source from a reader over the contents of a fl file
+    pub fn generate_reader<R: Read>(&self, mut reader: R) -> Result<String, Error> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        self.generate_str(&source)
+    }
+
+    /// Generates Rust source from the contents of a fl file into a writer
+    pub fn generate_writer<W: Write>(&self, source: &str, mut writer: W) -> Result<(), Error> {
+        let out = self.generate_str(source)?;
+        writer.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    /// Generates Rust tokens from the contents of a fl file, useful for procedural macros
+    pub fn generate_tokens(&self, source: &str) -> Result<TokenStream, Error> {
+        let ast = Parser::new(Lexer::new(&source::parseable(source))).parse()?;
+        let map = source::SourceMap::new(source);
+        gen::try_generate_tokens(&ast, Some(&map), &self.options, self.mapper())
+            .map(|(tokens, _)| tokens)
+    }
+
+    pub(crate) fn in_out_(
         &self,
-        inpath: P,
-        outpath: P,
-    ) -> Result<(), Box<dyn error::Error>> {
-        let content = fs::read_to_string(inpath)?;
-        let lexer = Lexer::new(&content);
-        let mut parser = Parser::new(lexer);
-        fs::write(
-            outpath,
-            gen::generate_with_directives_preamble(&parser.parse()),
-        )?;
+        inpath: &Path,
+        outpath: &Path,
+        options: &GeneratorOptions,
+    ) -> Result<(), Error> {
+        let content = fs::read_to_string(inpath).map_err(Error::io(inpath))?;
+        self.in_out_source(inpath, &content, outpath, options)
+    }
+
+    /// Same as `in_out_`, with the contents of `inpath` already read
+    pub(crate) fn in_out_source(
+        &self,
+        inpath: &Path,
+        content: &str,
+        outpath: &Path,
+        options: &GeneratorOptions,
+    ) -> Result<(), Error> {
+        if self.mapper.is_some() {
+            // the stamp can't account for custom mappings, only skip identical writes
+            let out = self.generate_file(inpath, content, options)?;
+            stamp::write_if_changed(outpath, &out).map_err(Error::io(outpath))?;
+            return Ok(());
+        }
+        let stamp = stamp::stamp(content, options);
+        if !options.force && stamp::is_current(outpath, &stamp) {
+            return Ok(());
+        }
+        let out = self.generate_file(inpath, content, options)?;
+        fs::write(outpath, format!("{}\n{}", stamp, out)).map_err(Error::io(outpath))?;
         Ok(())
     }
+
+    /// Generates the contents of a fl file, printing the warnings as `cargo:warning` directives
+    fn generate_file(
+        &self,
+        inpath: &Path,
+        content: &str,
+        options: &GeneratorOptions,
+    ) -> Result<String, Error> {
+        let (out, warnings) = self
+            .generate_source(content, options)
+            .map_err(|e| e.with_file(inpath))?;
+        for w in warnings {
+            println!("cargo:warning={}", w.with_file(inpath));
+        }
+        Ok(out)
+    }
+
+    fn generate_source(
+        &self,
+        content: &str,
+        options: &GeneratorOptions,
+    ) -> Result<(String, Vec<Warning>), Error> {
+        let parseable = source::parseable(content);
+        let mut parser = Parser::new(Lexer::new(&parseable));
+        let ast = parser.parse()?;
+        let source = source::SourceMap::new(content);
+        gen::try_generate(&ast, Some(&source), options, self.mapper())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn out_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(name)
+    }
+
+    /// Whether the generated code contains `code`, however either is formatted
+    fn has_code(out: &str, code: &str) -> bool {
+        let squash = |s: &str| s.split_whitespace().collect::<String>();
+        squash(out).contains(&squash(code))
+    }
+
     #[test]
     fn it_works() {
-        use super::*;
         let g = Generator::default();
-        g.in_out("fl_tests/fl.fl", "fl_tests/fl.rs")
+        g.in_out(Path::new("../tests/fl.fl"), &out_path("fl.rs"))
             .expect("Failed to generate rust from fl file!");
-        g.in_out("fl_tests/fl2.fl", "fl_tests/fl2.rs")
+        g.in_out(Path::new("../tests/fl2.fl"), &out_path("fl2.rs"))
             .expect("Failed to generate rust from fl file!");
-        g.in_out("fl_tests/unt.fl", "fl_tests/unt.rs")
+        g.in_out(Path::new("../tests/unt.fl"), &out_path("unt.rs"))
             .expect("Failed to generate rust from fl file!");
     }
+
+    #[test]
+    fn output_is_independent_of_previous_runs() {
+        let g = Generator::default();
+        let flex = fs::read_to_string("../tests/flex.fl").unwrap();
+        let first = g.generate_str(&flex).unwrap();
+        g.generate_str(&fs::read_to_string("../tests/gallery.fl").unwrap())
+            .unwrap();
+        assert_eq!(first, g.generate_str(&flex).unwrap());
+    }
+
+    #[test]
+    fn options_are_applied() {
+        let content = fs::read_to_string("../tests/classes.fl").unwrap();
+        let options = GeneratorOptions::default()
+            .imports(ImportStyle::Grouped)
+            .fltk_crate("my_fltk")
+            .field_visibility(FieldVisibility::Crate);
+        let out = Generator::with_options(options)
+            .generate_str(&content)
+            .unwrap();
+        let squashed: String = out.split_whitespace().collect();
+        assert!(squashed.starts_with("usemy_fltk::{browser::*,"));
+        assert!(squashed.contains("pub(crate)but1:Button,"));
+        assert!(!out.contains(gen::ALLOWS));
+        let out = Generator::with_options(
+            GeneratorOptions::default()
+                .preamble(true)
+                .imports(ImportStyle::None),
+        )
+        .generate_str(&content)
+        .unwrap();
+        assert!(out.starts_with(gen::ALLOWS));
+        assert!(!out.contains("use fltk::"));
+    }
+
+    #[test]
+    fn output_is_pretty_printed() {
+        let content = fs::read_to_string("../tests/classes.fl").unwrap();
+        let out = Generator::default()
+            .generate_source(&content, &GeneratorOptions::default())
+            .unwrap()
+            .0;
+        assert!(out.contains("\n\n        // Fl_Button but1\n        let mut but1 = Button::new(175, 230, 95, 45, None);\n"));
+        assert!(out.contains("}\n\nimpl UserInterface {\n"));
+        let out = Generator::default()
+            .generate_source(&content, &GeneratorOptions::default().pretty(false))
+            .unwrap()
+            .0;
+        assert!(!out.contains("Fl_Button"));
+        assert!(out.contains("let mut but1 = Button :: new"));
+    }
+
+    #[test]
+    fn anonymous_widgets_are_named_by_position() {
+        let content = fs::read_to_string("../tests/naming.fl").unwrap();
+        let out = Generator::default()
+            .generate_source(&content, &GeneratorOptions::default())
+            .unwrap()
+            .0;
+        assert!(out.contains("let mut window0_flex1_button0 = Button::new"));
+        assert!(out.contains("let mut window0_flex1_button1 = Button::new"));
+        assert!(out.contains("pub struct Ui {\n    pub ok: Button,\n}"));
+
+        let options = GeneratorOptions::default()
+            .widget_naming(WidgetNaming::Label)
+            .anonymous_fields(true);
+        let out = Generator::default()
+            .generate_source(&content, &options)
+            .unwrap()
+            .0;
+        assert!(out.contains("let mut click_me = Button::new"));
+        assert!(out.contains("let mut click_me_2 = Button::new"));
+        assert!(out.contains("pub window0: Window,"));
+        assert!(out.contains("pub click_me_2: Button,"));
+    }
+
+    #[test]
+    fn generates_in_memory() {
+        let content = fs::read_to_string("../tests/fl.fl").unwrap();
+        let g = Generator::default();
+        let out = g.generate_str(&content).unwrap();
+        assert_eq!(g.generate_reader(content.as_bytes()).unwrap(), out);
+        let mut buf = vec![];
+        g.generate_writer(&content, &mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), out);
+        assert!(matches!(
+            g.generate_str("Function {make_window()"),
+            Err(Error::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn generates_directories() {
+        let src = out_path("fl2rust_dir_src");
+        let out = out_path("fl2rust_dir_out");
+        let _ = fs::remove_dir_all(&src);
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::copy("../tests/fl.fl", src.join("MainWindow.fl")).unwrap();
+        fs::copy("../tests/flex.fl", src.join("nested/flex-layout.fl")).unwrap();
+        fs::write(src.join("notes.txt"), "").unwrap();
+
+        let g = Generator::default();
+        g.in_out_dir(&src, &out).unwrap();
+        let mod_rs = fs::read_to_string(out.join("mod.rs")).unwrap();
+        assert!(mod_rs.contains("pub mod main_window {\n    include!(\"main_window.rs\");\n}"));
+        assert!(!mod_rs.contains("flex_layout"));
+        assert!(!fs::read_to_string(out.join("main_window.rs"))
+            .unwrap()
+            .contains("#![allow"));
+
+        g.in_out_dir_matching(&src, &out, &["**/*.fl"]).unwrap();
+        let mod_rs = fs::read_to_string(out.join("mod.rs")).unwrap();
+        assert_eq!(mod_rs.matches("#[allow(unused_variables, ").count(), 2);
+        assert!(mod_rs.contains("pub mod nested_flex_layout {"));
+        assert!(out.join("nested_flex_layout.rs").exists());
+
+        fs::copy("../tests/flex.fl", src.join("main_window.fl")).unwrap();
+        let err = g.in_out_dir(&src, &out).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DuplicateModule { ref module, ref files }
+                if module == "main_window"
+                    && files == &[src.join("MainWindow.fl"), src.join("main_window.fl")]
+        ));
+    }
+
+    #[test]
+    fn compiles_into_out_dir() {
+        let dir = out_path("fl2rust_build");
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("OUT_DIR", &dir);
+        let out = build::compile("../tests/col.fl").unwrap();
+        assert_eq!(out, dir.join("col.rs"));
+        // only the `.fl` extension is replaced
+        fs::copy("../tests/col.fl", dir.join("col.dark.fl")).unwrap();
+        let dark = build::compile(dir.join("col.dark.fl")).unwrap();
+        assert_eq!(dark, dir.join("col.dark.rs"));
+        assert!(dark.exists());
+        let generated = fs::read_to_string(out).unwrap();
+        assert!(!generated.contains("#![allow"));
+        assert!(generated.contains("pub fn make_window"));
+        let files = source::SourceMap::new(&fs::read_to_string("../tests/cube.fl").unwrap())
+            .files()
+            .to_vec();
+        assert_eq!(files, ["pixmaps/black_checker.png"]);
+    }
+
+    #[test]
+    fn unchanged_output_is_not_rewritten() {
+        let out = out_path("fl2rust_stamp.rs");
+        let _ = fs::remove_file(&out);
+        let g = Generator::default();
+        g.in_out(Path::new("../tests/fl.fl"), &out).unwrap();
+        let generated = fs::read_to_string(&out).unwrap();
+        assert!(generated.starts_with("// fl2rust stamp "));
+
+        let stamp_line = generated.lines().next().unwrap().to_string();
+        fs::write(&out, format!("{}\n// edited", stamp_line)).unwrap();
+        g.in_out(Path::new("../tests/fl.fl"), &out).unwrap();
+        assert!(fs::read_to_string(&out).unwrap().ends_with("// edited"));
+
+        let g = Generator::with_options(GeneratorOptions::default().pretty(false));
+        g.in_out(Path::new("../tests/fl.fl"), &out).unwrap();
+        let other = fs::read_to_string(&out).unwrap();
+        assert_ne!(other.lines().next().unwrap(), stamp_line);
+
+        fs::write(
+            &out,
+            format!("{}\n// edited", other.lines().next().unwrap()),
+        )
+        .unwrap();
+        let g = Generator::with_options(GeneratorOptions::default().pretty(false).force(true));
+        g.in_out(Path::new("../tests/fl.fl"), &out).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), other);
+    }
+
+    #[test]
+    fn widgets_are_mapped() {
+        struct Extras;
+        impl WidgetMapper for Extras {
+            fn map(&self, fluid_type: &str) -> Option<WidgetSpec> {
+                match fluid_type {
+                    "Fl_Button" => Some(WidgetSpec {
+                        ctor: "Toggle::default().with_size".to_string(),
+                        ..WidgetSpec::new("extras::Toggle")
+                    }),
+                    "Fl_Spinner" => None,
+                    _ => DefaultWidgetMapper.map(fluid_type),
+                }
+            }
+        }
+        let g = Generator::default().widget_mapper(Extras);
+        let content = fs::read_to_string("../tests/classes.fl").unwrap();
+        let out = g.generate_str(&content).unwrap();
+        assert!(out.contains("pub but1: extras::Toggle,"));
+        assert!(out.contains("let mut but1 = Toggle::default().with_size(175, 230, 95, 45, None);"));
+        assert!(out.contains("window0.end();"));
+
+        let err = g
+            .generate_str(&content.replace("Fl_Button but2", "Fl_Spinner but2"))
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedWidget { ref typ, .. } if typ == "Fl_Spinner"));
+        assert_eq!(err.location().map(|l| l.line), Some(30));
+    }
+
+    #[test]
+    fn registry_covers_fluid_widgets() {
+        let g = Generator::with_options(GeneratorOptions::default().fltk_crate("my_fltk"));
+        let content = fs::read_to_string("../tests/registry.fl").unwrap();
+        let out = g.generate_str(&content).unwrap();
+        assert!(out.contains("let mut bar = SysMenuBar::new(0, 0, 400, 20, None);"));
+        assert!(out.contains("bar.end();"));
+        assert!(out.contains("let mut slider = HorValueSlider::new(0, 20, 100, 20, None);"));
+        assert!(out.contains("slider.set_value(5 as _);"));
+        assert!(out.contains("SimpleTerminal::new("));
+        assert!(out.contains("let mut term2 = my_fltk::terminal::Terminal::new("));
+        assert!(out.contains("tree.end();"));
+
+        let err = Generator::default()
+            .generate_str(&content.replace("Fl_Tree tree", "Fl_Foo_Bar tree"))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "22:5: unsupported widget `Fl_Foo_Bar` in make/Fl_Window/Fl_Foo_Bar tree"
+        );
+    }
+
+    #[test]
+    fn unsupported_properties_are_dropped() {
+        let (out, warnings) = Generator::default()
+            .generate_with_warnings(&fs::read_to_string("../tests/properties.fl").unwrap())
+            .unwrap();
+        assert!(out.contains("win.size_range(100, 100, 0, 0);"));
+        assert!(out.contains("input.set_text_size(12);"));
+        assert!(out.contains("input.set_value(\"hello\");"));
+        assert!(!out.contains("frame.set_text_size"));
+        assert!(!out.contains("frame.set_minimum"));
+        assert!(!out.contains("group.size_range"));
+        assert!(!out.contains("group.set_type"));
+        let warnings: Vec<_> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "11:25: property `textsize` isn't supported by `Frame`, dropped from make/Fl_Window win/Fl_Box frame",
+                "11:37: property `minimum` isn't supported by `Frame`, dropped from make/Fl_Window win/Fl_Box frame",
+                "14:26: property `type` isn't supported by `Group`, dropped from make/Fl_Window win/Fl_Group group",
+                "14:40: property `size_range` isn't supported by `Group`, dropped from make/Fl_Window win/Fl_Group group",
+            ]
+        );
+    }
+
+    #[test]
+    fn bitmasks_are_typed() {
+        let g = Generator::default();
+        let content = fs::read_to_string("../tests/bitmasks.fl").unwrap();
+        let out = g.generate_str(&content).unwrap();
+        assert!(!out.contains("unsafe"));
+        assert!(out.contains("but.set_align(Align::Center);"));
+        assert!(out
+            .contains("but.set_trigger(CallbackTrigger::Release | CallbackTrigger::NotChanged);"));
+        assert!(out.contains("but.set_shortcut(Shortcut::Ctrl | Shortcut::Shift | 's');"));
+        assert!(
+            out.contains("but2.set_align(Align::Left | Align::Inside | Align::TextNextToImage);")
+        );
+        assert!(out.contains("but2.set_trigger(CallbackTrigger::Never);"));
+        assert!(out.contains("but2.set_shortcut(Shortcut::None | Key::F1);"));
+
+        let err = g
+            .generate_str(&content.replace("align 308", "align 32768"))
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidProperty { ref property, .. } if property == "align"));
+    }
+
+    #[test]
+    fn colors_are_named() {
+        let content = fs::read_to_string("../tests/colors.fl").unwrap();
+        let out = Generator::default().generate_str(&content).unwrap();
+        assert!(out.contains("input.set_color(Color::Black);"));
+        assert!(out.contains("input.set_selection_color(Color::from_hex(0xaaff00));"));
+        assert!(out.contains("input.set_label_color(Color::BackGround2);"));
+        assert!(out.contains("input.set_text_color(Color::by_index(17));"));
+
+        let out = Generator::with_options(GeneratorOptions::default().raw_colors(true))
+            .generate_str(&content)
+            .unwrap();
+        assert!(out.contains("input.set_color(Color::by_index(56));"));
+        assert!(out.contains("input.set_selection_color(Color::from_rgbi(2868838400));"));
+    }
+
+    #[test]
+    fn top_level_groups_are_resizable() {
+        let (out, warnings) = Generator::default()
+            .generate_with_warnings(&fs::read_to_string("../tests/group.fl").unwrap())
+            .unwrap();
+        assert!(warnings.is_empty());
+        assert!(out.contains("panel.make_resizable(true);"));
+        assert!(out.contains("panel.resizable(&"));
+    }
+
+    #[test]
+    fn fonts_are_named() {
+        let content = fs::read_to_string("../tests/fonts.fl").unwrap();
+        let (out, warnings) = Generator::default()
+            .generate_with_warnings(&content)
+            .unwrap();
+        assert!(out.contains("input.set_label_font(Font::CourierBold);"));
+        assert!(out.contains("input.set_text_font(Font::by_index(17));"));
+        assert!(matches!(
+            warnings.as_slice(),
+            [Warning::UnknownFont { index: 17, .. }]
+        ));
+
+        let (out, warnings) =
+            Generator::with_options(GeneratorOptions::default().font_lookup("crate::fonts::font"))
+                .generate_with_warnings(&content)
+                .unwrap();
+        assert!(out.contains("input.set_text_font(crate::fonts::font(17));"));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn strings_are_unescaped() {
+        let out = Generator::default()
+            .generate_str(&fs::read_to_string("../tests/strings.fl").unwrap())
+            .unwrap();
+        assert!(out.contains(r#"but.set_label("say \"hi\" \\ to a{b {c} AA é");"#));
+        assert!(out.contains(r#"but.set_tooltip("line one\nline two\tthree");"#));
+        assert!(out.contains(r#"but.set_id("myAid");"#));
+    }
+
+    #[test]
+    fn menu_labels_are_escaped() {
+        let g = Generator::default();
+        let out = g
+            .generate_str(&fs::read_to_string("../tests/menu2.fl").unwrap())
+            .unwrap();
+        let item = "Shortcut::None, MenuFlag::Normal, |_| ())";
+        assert!(has_code(
+            &out,
+            &format!(r#"add("submenu2/Save\\/Export", {}"#, item)
+        ));
+        assert!(has_code(
+            &out,
+            &format!(r#"add("submenu2/\\_hidden\\\\item", {}"#, item)
+        ));
+        // `|` separates items in `add_choice` only
+        assert!(has_code(
+            &out,
+            &format!(r#"add("submenu2/Cut|Paste", {}"#, item)
+        ));
+        assert!(!out.contains("add_choice"));
+        assert!(has_code(
+            &out,
+            r#"let idx = window0_menubar0.add("&sub1", Shortcut::None, MenuFlag::Submenu, |_| ());
+            window0_menubar0.at(idx).unwrap().set_label_color"#
+        ));
+        assert!(!out.contains("find_item"));
+
+        let out = g
+            .generate_str(&fs::read_to_string("../tests/menu3.fl").unwrap())
+            .unwrap();
+        assert!(has_code(&out, r#"add("In\\/Out/Read\\/Write", "#));
+    }
+
+    #[test]
+    fn menu_item_properties_are_translated() {
+        let g = Generator::default();
+        let (out, warnings) = g
+            .generate_with_warnings(&fs::read_to_string("../tests/menu_item.fl").unwrap())
+            .unwrap();
+        let item = "window0_menubar1.at(idx).unwrap().";
+        assert!(has_code(
+            &out,
+            r#"add(&tr!("File/Save"), Shortcut::None, MenuFlag::MenuDivider, |_| ())"#
+        ));
+        assert!(has_code(
+            &out,
+            &format!("{}deactivate(); let exit_available = false;", item)
+        ));
+        assert!(has_code(&out, &format!("{}hide();", item)));
+        assert!(has_code(
+            &out,
+            &format!(
+                r#"add(&tr!("Edit/Wrap"), Shortcut::None, MenuFlag::Toggle, |_| ()); {}set();"#,
+                item
+            )
+        ));
+        assert!(has_code(
+            &out,
+            r#"add(&tr!("View"), Shortcut::Alt | 'v', MenuFlag::Submenu, |_| ())"#
+        ));
+        assert!(has_code(
+            &out,
+            &format!("{}add_image(Some(SharedImage::load", item)
+        ));
+        assert!(has_code(&out, "window0_choice2.at(idx).unwrap().set();"));
+        let dropped: Vec<_> = warnings
+            .iter()
+            .map(|w| match w {
+                Warning::UnsupportedProperty { property, typ, .. } => {
+                    (property.as_str(), typ.as_str())
+                }
+                _ => panic!("unexpected warning: {}", w),
+            })
+            .collect();
+        assert_eq!(
+            dropped,
+            [("tooltip", "MenuItem"), ("user_data", "MenuItem")]
+        );
+        // the class following `i18n_type` is kept, so the source index pairs with the ast
+        let lines: Vec<_> = warnings
+            .iter()
+            .map(|w| w.location().map(|l| l.line))
+            .collect();
+        assert_eq!(lines, [Some(55), Some(64)]);
+    }
+
+    #[test]
+    fn input_choice_items_are_added() {
+        let fl = fs::read_to_string("../tests/input_choice.fl").unwrap();
+        let out = Generator::default().generate_str(&fl).unwrap();
+        assert!(has_code(
+            &out,
+            r#"let idx = size.menu_button().add("Small", Shortcut::None, MenuFlag::Normal, |_| ());"#
+        ));
+        assert!(has_code(
+            &out,
+            "let mut large = size.menu_button().at(large).unwrap();"
+        ));
+        assert!(!has_code(&out, "size.end()"));
+    }
+
+    #[test]
+    fn named_menu_items_are_exposed() {
+        let out = Generator::default()
+            .generate_str(&fs::read_to_string("../tests/menu_item.fl").unwrap())
+            .unwrap();
+        let file = syn::parse_file(&out).unwrap();
+        let fields = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Struct(s) if s.ident == "UserInterface" => Some(&s.fields),
+                _ => None,
+            })
+            .unwrap();
+        // a public `MenuItem` field
+        let is_item_field = |name: &str| {
+            fields.iter().any(|f| {
+                f.ident.as_ref().map_or(false, |i| i == name)
+                    && matches!(f.vis, syn::Visibility::Public(_))
+                    && matches!(&f.ty, syn::Type::Path(p) if p.path.is_ident("MenuItem"))
+            })
+        };
+        assert!(is_item_field("save_item"));
+        assert!(is_item_field("view_menu"));
+        assert!(out.contains("Self {\n            my_win,\n            btn,\n            save_item,\n            view_menu,\n        }"));
+
+        // handles are taken once every item is added, adding can reallocate the menu
+        let last_add = out.rfind("window0_menubar1\n            .add(").unwrap();
+        let handle = out
+            .find("let mut save_item = window0_menubar1.at(save_item).unwrap();")
+            .unwrap();
+        assert!(last_add < handle);
+        assert!(out[..last_add].contains("let save_item = idx;"));
+    }
+
+    #[test]
+    fn grids_are_laid_out() {
+        let g = Generator::default();
+        let fl = fs::read_to_string("../tests/grid.fl").unwrap();
+        let (out, warnings) = g.generate_with_warnings(&fl).unwrap();
+        assert!(has_code(
+            &out,
+            "grid.set_layout(3, 2); grid.set_margin(10, 10, 10, 10); grid.set_gap(5, 10);"
+        ));
+        assert!(has_code(
+            &out,
+            "grid.set_row_height(1, 30); grid.set_row_weight(0, 0);"
+        ));
+        assert!(!out.contains("set_row_weight(2"));
+        assert!(has_code(&out, "grid.set_col_gap(0, 20);"));
+        assert!(has_code(
+            &out,
+            "grid.set_widget_ext(&mut window0_grid0_frame0, 0, 0, GridAlign::RIGHT).unwrap();"
+        ));
+        assert!(has_code(
+            &out,
+            "grid.set_widget(&mut login, 2, 0..2).unwrap();"
+        ));
+        // cells are placed once all children are created
+        assert!(out.find("set_widget").unwrap() > out.find("Button::new").unwrap());
+        assert!(matches!(
+            &warnings[..],
+            [Warning::UnsupportedProperty { property, location: Some(location), .. }]
+                if property == "min_size" && location.line == 32
+        ));
+
+        let err = g
+            .generate_str(&fl.replace("colspan 2", "colspan 3"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidProperty { ref property, ref value, .. }
+                if property == "location" && value == "2 0"
+        ));
+    }
+
+    #[test]
+    fn flex_children_are_fixed_by_handle() {
+        let g = Generator::default();
+        let fl = fs::read_to_string("../tests/flex2.fl").unwrap();
+        let out = g.generate_str(&fl).unwrap();
+        assert!(!out.contains(".child("));
+        assert!(has_code(
+            &out,
+            "toolbar.set_pad(4); toolbar.set_margins(2, 2, 2, 2);"
+        ));
+        // nested flexes are complete before being fixed in their parent
+        assert!(has_code(
+            &out,
+            "search.end(); search.recalc(); toolbar.fixed(&search, 60);
+            toolbar.end(); toolbar.recalc(); window0_flex0.fixed(&toolbar, 30);"
+        ));
+        assert!(has_code(
+            &out,
+            "window0_flex0.fixed(&status, 25); window0_flex0.end(); window0_flex0.recalc();"
+        ));
+        let out = g
+            .generate_str(&fl.replace("margin {2 2 2 2}", "margin {3}"))
+            .unwrap();
+        assert!(out.contains("toolbar.set_margin(3);"));
+
+        let err = g
+            .generate_str(&fl.replace("{1 2 60}", "{1 3 60}"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidProperty { ref widget_path, ref property, location: Some(ref location), .. }
+                if widget_path == "make_window/Fl_Window/Fl_Flex/Fl_Flex toolbar"
+                    && property == "fixed_size_tuples"
+                    && location.line == 17
+        ));
+    }
+
+    #[test]
+    fn pack_gaps_are_spacings() {
+        let (out, warnings) = Generator::default()
+            .generate_with_warnings(&fs::read_to_string("../tests/pack.fl").unwrap())
+            .unwrap();
+        assert!(warnings.is_empty());
+        assert!(out.contains("buttons.set_spacing(5);"));
+        assert!(!out.contains("set_pad"));
+    }
+
+    #[test]
+    fn window_properties_are_translated() {
+        let fl = fs::read_to_string("../tests/window.fl").unwrap();
+        let options = GeneratorOptions::default();
+        let out = Generator::default().generate_str(&fl).unwrap();
+        assert!(has_code(
+            &out,
+            "window0.set_override(); window0.fullscreen(true);"
+        ));
+        assert!(has_code(
+            &out,
+            r#"window0.set_icon(Some(SharedImage::load("../images/icon.png")"#
+        ));
+        assert!(out.contains("window0.hotspot(&play);"));
+        assert!(!out.contains("center_screen") && !out.contains("free_position"));
+
+        // only the top-level window is moved, subwindows are positioned in their parent
+        let out =
+            Generator::with_options(options.clone().window_position(WindowPosition::Centered))
+                .generate_str(&fl)
+                .unwrap();
+        assert!(has_code(
+            &out,
+            "let mut window0 = Window::new(2450, 310, 320, 240, None);
+            let mut window0 = window0.center_screen();"
+        ));
+        assert_eq!(out.matches("center_screen").count(), 1);
+        let out = Generator::with_options(options.window_position(WindowPosition::WindowManager))
+            .generate_str(&fl)
+            .unwrap();
+        assert!(has_code(&out, "None); window0.free_position();"));
+        assert_eq!(out.matches("free_position").count(), 1);
+    }
+
+    #[test]
+    fn tables_and_trees_are_configured() {
+        let fl = fs::read_to_string("../tests/table.fl").unwrap();
+        let (out, warnings) = Generator::default().generate_with_warnings(&fl).unwrap();
+        assert!(has_code(
+            &out,
+            "stock.set_rows(20); stock.set_cols(4); stock.set_col_header(true);
+            stock.set_row_height_all(25); stock.set_col_resize(true);"
+        ));
+        assert!(has_code(
+            &out,
+            "orders.set_cols(3); orders.set_row_header(false);
+            orders.set_type(TableRowSelectMode::Multi);"
+        ));
+        assert!(!out.contains("orders.set_rows"));
+        assert!(out.contains("files.set_item_label_size(12);"));
+        assert!(has_code(
+            &out,
+            r#"files.set_root_label("Project files"); files.set_show_root(false);
+            files.set_connector_style(TreeConnectorStyle::Dotted);"#
+        ));
+        // directives are stripped from the emitted comments
+        assert!(!out.contains("fl2rust:"));
+        assert!(out.contains("// Saves the stock levels"));
+        let dropped: Vec<_> = warnings
+            .iter()
+            .map(|w| match w {
+                Warning::UnsupportedProperty {
+                    property,
+                    typ,
+                    location,
+                    ..
+                } => (
+                    property.as_str(),
+                    typ.as_str(),
+                    location.as_ref().unwrap().line,
+                ),
+                _ => panic!("unexpected warning: {}", w),
+            })
+            .collect();
+        assert_eq!(dropped, [("editable", "Table", 11), ("rows", "Button", 26)]);
+
+        let options = GeneratorOptions::default()
+            .smart_tables(true)
+            .table_size(5, 2);
+        let (out, warnings) = Generator::with_options(options)
+            .generate_with_warnings(&fl)
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(has_code(
+            &out,
+            "let mut stock = fltk_table::SmartTable::new(10, 10, 300, 380, None);
+            let mut stock = stock.with_opts(fltk_table::TableOpts {
+                rows: 20,
+                cols: 4,
+                editable: true,
+                ..Default::default()
+            });"
+        ));
+        assert!(!out.contains("stock.set_rows"));
+        assert!(has_code(&out, "orders.set_rows(5); orders.set_cols(3);"));
+
+        let err = Generator::default()
+            .generate_str(&fl.replace("show_root=false", "show_root=no"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidProperty { ref property, ref value, location: Some(ref location), .. }
+                if property == "comment" && value == "show_root=no" && location.line == 21
+        ));
+    }
+
+    #[test]
+    fn text_widgets_get_buffers() {
+        let fl = fs::read_to_string("../tests/flex2.fl").unwrap();
+        let out = Generator::default().generate_str(&fl).unwrap();
+        assert!(has_code(
+            &out,
+            "let mut editor_buffer = TextBuffer::default();
+            editor.set_buffer(editor_buffer.clone());
+            let mut editor_style_buffer = TextBuffer::default();"
+        ));
+        assert!(has_code(
+            &out,
+            "(toolbar, search, editor, editor_buffer, editor_style_buffer, status)"
+        ));
+        assert!(!out.contains("fl2rust:"));
+
+        let out = Generator::with_options(GeneratorOptions::default().text_buffers(false))
+            .generate_str(&fl)
+            .unwrap();
+        assert!(!out.contains("editor_buffer"));
+        assert!(has_code(
+            &out,
+            "(toolbar, search, editor, editor_style_buffer, status)"
+        ));
+
+        // unknown directives are dropped like on any other widget
+        let (out, warnings) = Generator::default()
+            .generate_with_warnings(&fl.replace("style_buffer", "style_buffer wrap"))
+            .unwrap();
+        assert!(out.contains("let mut editor_style_buffer = TextBuffer::default();"));
+        assert!(matches!(
+            &warnings[..],
+            [Warning::UnsupportedProperty { property, typ, location: Some(location), .. }]
+                if property == "wrap" && typ == "TextEditor" && location.line == 40
+        ));
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let path = out_path("bad_value.fl");
+        fs::write(
+            &path,
+            r#"class UserInterface {open
+} {
+  Function {make_window()} {open
+  } {
+    Fl_Window {} {open
+      xywh {138 161 440 355} type Double visible
+    } {
+      Fl_Flex what {open
+        xywh {5 5 390 335}
+      } {
+        Fl_Button {} {
+          label {Click me}
+          xywh {175 230 95 45} value yes
+        }
+      }
+    }
+  }
+}"#,
+        )
+        .unwrap();
+        let err = Generator::default()
+            .in_out(&path, &out_path("bad_value.rs"))
+            .unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!((location.line, location.col), (13, 32));
+        assert_eq!(location.file.as_deref(), Some(path.as_path()));
+        assert!(matches!(
+            err,
+            Error::InvalidProperty { ref widget_path, ref property, .. }
+                if widget_path == "UserInterface::make_window/Fl_Window/Fl_Flex what/Fl_Button"
+                    && property == "value"
+        ));
+
+        let err = Generator::default().generate_source(
+            "Function {make_window()} {open\n} {\n  Fl_Window type {open\n    xywh {0 0 10 10}\n  } {}\n}",
+            &GeneratorOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidIdentifier { ref ident, .. } if ident == "type"));
+        assert_eq!(err.location().map(|l| l.line), Some(3));
+
+        let err = Generator::default()
+            .generate_source("Function {make_window()", &GeneratorOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, Error::Parse { .. }));
+
+        for property in ["box", "labeltype"] {
+            let err = Generator::default()
+                .generate_str(&format!(
+                    "Function {{make_window()}} {{open\n}} {{\n  Fl_Button {{}} {{\n    xywh {{0 0 10 10}} {} {{}}\n  }}\n}}",
+                    property
+                ))
+                .unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidProperty { property: ref p, ref value, .. } if p == property && value.is_empty())
+            );
+        }
+
+        let missing = out_path("missing.fl");
+        let err = Generator::default()
+            .in_out(&missing, &out_path("missing.rs"))
+            .unwrap_err();
+        assert!(matches!(err, Error::Io { path: Some(ref p), .. } if *p == missing));
+        assert!(err.to_string().starts_with(&missing.display().to_string()));
+    }
+
+    #[test]
+    fn mismatched_sources_are_not_located() {
+        let button = "Function {make()} {open\n} {\n  Fl_Button {} {\n    xywh {0 0 10 10} value yes\n  }\n}";
+        let ast = Parser::new(Lexer::new(button)).parse().unwrap();
+        let generate = |source: &str| {
+            let map = source::SourceMap::new(source);
+            gen::try_generate(
+                &ast,
+                Some(&map),
+                &GeneratorOptions::default(),
+                &DefaultWidgetMapper,
+            )
+            .unwrap_err()
+        };
+        assert_eq!(generate(button).location().map(|l| l.line), Some(4));
+        // another node at the same position isn't taken for the button
+        let input = button.replace("Fl_Button", "Fl_Input");
+        assert_eq!(generate(&input).location(), None);
+    }
+
+    #[test]
+    fn invalid_code_is_reported() {
+        let err = Generator::default().generate_source(
+            "Function {make_window()} {open\n} {\n  Fl_Button {} {\n    callback {|_| println!(\"hi\"}\n    xywh {0 0 10 10}\n  }\n}",
+            &GeneratorOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidCode { ref widget_path, ref property, .. }
+                if widget_path == "make_window/Fl_Button" && property == "callback"
+        ));
+        assert_eq!(err.location().map(|l| l.line), Some(4));
+        assert!(err.to_string().ends_with("in `|_| println!(\"hi\"`"));
+
+        // fluid escapes `#` and backslashes in code
+        let out = Generator::default()
+            .generate_str(&fs::read_to_string("../tests/cube.fl").unwrap())
+            .unwrap();
+        assert!(out.contains("# include < stdio . h >"));
+
+        let tokens = Generator::default()
+            .generate_tokens(&fs::read_to_string("../tests/flex.fl").unwrap())
+            .unwrap();
+        assert!(tokens.to_string().contains("pub fn make_window"));
+    }
+
+    #[test]
+    fn generation_is_thread_safe() {
+        let content = fs::read_to_string("../tests/menu4.fl").unwrap();
+        let expected = Generator::default().generate_str(&content).unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let content = content.clone();
+                std::thread::spawn(move || Generator::default().generate_str(&content).unwrap())
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), expected);
+        }
+    }
 }
@@ -1,6 +1,7 @@
 #![doc = include_str!("../README.md")]
 #![allow(clippy::needless_doctest_main)]
 
+use fl2rust::{Generator, GeneratorOptions};
 use fluid_parser::lexer::Lexer;
 use fluid_parser::parser::Parser;
 use std::error;
@@ -9,11 +10,21 @@ use std::fs;
 fn main() -> Result<(), Box<dyn error::Error>> {
     let args: Vec<String> = std::env::args().collect();
     let f = fs::read_to_string(&args[1])?;
-    let lexer = Lexer::new(&f);
-    let mut parser = Parser::new(lexer);
-    let ast = parser.parse();
-    println!("{}", fl2rust::gen::generate_with_directives_preamble(&ast));
+    let g = Generator::with_options(GeneratorOptions::default().preamble(true));
+    match g.generate_with_warnings(&f) {
+        Ok((out, warnings)) => {
+            for w in warnings {
+                eprintln!("warning: {}: {}", args[1], w);
+            }
+            print!("{}", out)
+        }
+        Err(e) => {
+            eprintln!("{}: {}", args[1], e);
+            std::process::exit(1);
+        }
+    }
     if args.contains(&"--print-ast".to_string()) {
+        let ast = Parser::new(Lexer::new(&f)).parse()?;
         println!("{:#?}", ast);
     }
     Ok(())
@@ -0,0 +1,73 @@
+//! Mapping of fluid widget classes to Rust types
+
+use crate::widgets::{self, Capabilities, WidgetCategory};
+
+/// How a fluid widget class is generated in Rust
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WidgetSpec {
+    /// Path of the Rust type, e.g. `Button` or `fltk_extras::button::Toggle`.
+    /// Paths starting with `fltk::` are resolved against the configured fltk crate.
+    pub path: String,
+    /// Constructor, called with `x, y, w, h` and a `None` label, e.g. `Button::new`
+    pub ctor: String,
+    /// Enum set by the `type` property, e.g. `ButtonType`
+    pub type_enum: Option<String>,
+    /// The kind of widget
+    pub category: WidgetCategory,
+    /// Setters the type supports, properties needing others are dropped with a warning
+    pub capabilities: Capabilities,
+}
+
+impl WidgetSpec {
+    /// Creates the spec of a plain widget constructed with `<path>::new`.
+    /// Its capabilities are unknown, so every property is emitted.
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            ctor: format!("{}::new", path),
+            type_enum: None,
+            category: WidgetCategory::Widget,
+            capabilities: Capabilities::ALL,
+        }
+    }
+}
+
+/// Maps fluid widget classes, like `Fl_Button`, to Rust types.
+///
+/// A custom mapper can handle in-house or third-party widgets and defer the others to `DefaultWidgetMapper`:
+/// ```rust
+/// use fl2rust::{DefaultWidgetMapper, WidgetMapper, WidgetSpec};
+///
+/// struct Extras;
+///
+/// impl WidgetMapper for Extras {
+///     fn map(&self, fluid_type: &str) -> Option<WidgetSpec> {
+///         match fluid_type {
+///             "Fl_Light_Button" => Some(WidgetSpec::new("fltk_extras::button::Toggle")),
+///             _ => DefaultWidgetMapper.map(fluid_type),
+///         }
+///     }
+/// }
+///
+/// let g = fl2rust::Generator::default().widget_mapper(Extras);
+/// ```
+pub trait WidgetMapper: Send + Sync {
+    /// Gets the Rust spec of a fluid widget class, `None` if the class isn't supported
+    fn map(&self, fluid_type: &str) -> Option<WidgetSpec>;
+}
+
+/// The fltk-rs mapping used by default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultWidgetMapper;
+
+impl WidgetMapper for DefaultWidgetMapper {
+    fn map(&self, fluid_type: &str) -> Option<WidgetSpec> {
+        let info = widgets::lookup(fluid_type)?;
+        Some(WidgetSpec {
+            type_enum: info.type_enum.map(str::to_string),
+            category: info.category,
+            capabilities: info.capabilities(),
+            ..WidgetSpec::new(info.path)
+        })
+    }
+}
@@ -0,0 +1,196 @@
+/// How the generated code imports fltk items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportStyle {
+    /// One `use fltk::module::*;` line per fltk module
+    #[default]
+    Glob,
+    /// A single grouped `use fltk::{module::*, ...};` statement
+    Grouped,
+    /// No imports, the including module is expected to provide them
+    None,
+}
+
+/// Visibility of the fields of generated structs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldVisibility {
+    /// `pub`
+    #[default]
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// Private to the generated module
+    Private,
+}
+
+impl FieldVisibility {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            FieldVisibility::Public => "pub ",
+            FieldVisibility::Crate => "pub(crate) ",
+            FieldVisibility::Private => "",
+        }
+    }
+}
+
+/// How anonymous widgets are named in the generated code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WidgetNaming {
+    /// From the type and position of the widget and its parents, e.g. `window0_flex1_button2`
+    #[default]
+    Path,
+    /// From the label of the widget, e.g. `click_me`, falling back to `Path` for unlabeled widgets
+    Label,
+}
+
+/// Where top-level windows open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowPosition {
+    /// At the screen position stored in the fl file, as placed on the desktop of the designer
+    #[default]
+    Designer,
+    /// Centered on the screen
+    Centered,
+    /// Wherever the window manager places them
+    WindowManager,
+}
+
+/// Options controlling the generated code
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    pub(crate) preamble: bool,
+    pub(crate) imports: ImportStyle,
+    pub(crate) i18n_macro: String,
+    pub(crate) fltk_crate: String,
+    pub(crate) field_visibility: FieldVisibility,
+    pub(crate) pretty: bool,
+    pub(crate) widget_naming: WidgetNaming,
+    pub(crate) anonymous_fields: bool,
+    pub(crate) force: bool,
+    pub(crate) raw_colors: bool,
+    pub(crate) font_lookup: Option<String>,
+    pub(crate) window_position: WindowPosition,
+    pub(crate) smart_tables: bool,
+    pub(crate) table_size: Option<(i32, i32)>,
+    pub(crate) text_buffers: bool,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            preamble: false,
+            imports: ImportStyle::default(),
+            i18n_macro: "tr".to_string(),
+            fltk_crate: "fltk".to_string(),
+            field_visibility: FieldVisibility::default(),
+            pretty: true,
+            widget_naming: WidgetNaming::default(),
+            anonymous_fields: false,
+            force: false,
+            raw_colors: false,
+            font_lookup: None,
+            window_position: WindowPosition::default(),
+            smart_tables: false,
+            table_size: None,
+            text_buffers: true,
+        }
+    }
+}
+
+impl GeneratorOptions {
+    /// Emit the `ALLOWS` lint directives at the top of the output
+    pub fn preamble(mut self, flag: bool) -> Self {
+        self.preamble = flag;
+        self
+    }
+
+    /// Set how fltk items are imported
+    pub fn imports(mut self, style: ImportStyle) -> Self {
+        self.imports = style;
+        self
+    }
+
+    /// Set the macro used to translate strings when i18n is enabled in the fl file, defaults to `tr`
+    pub fn i18n_macro(mut self, path: &str) -> Self {
+        self.i18n_macro = path.to_string();
+        self
+    }
+
+    /// Set the path of the fltk crate, useful when fltk is re-exported under another name
+    pub fn fltk_crate(mut self, path: &str) -> Self {
+        self.fltk_crate = path.to_string();
+        self
+    }
+
+    /// Set the visibility of the fields of generated structs
+    pub fn field_visibility(mut self, vis: FieldVisibility) -> Self {
+        self.field_visibility = vis;
+        self
+    }
+
+    /// Format the generated source like rustfmt would, with a comment naming the fl widget of each block.
+    /// Enabled by default, has no effect on generated tokens
+    pub fn pretty(mut self, flag: bool) -> Self {
+        self.pretty = flag;
+        self
+    }
+
+    /// Set how anonymous widgets are named
+    pub fn widget_naming(mut self, naming: WidgetNaming) -> Self {
+        self.widget_naming = naming;
+        self
+    }
+
+    /// Expose anonymous widgets as fields of the generated structs, like named widgets
+    pub fn anonymous_fields(mut self, flag: bool) -> Self {
+        self.anonymous_fields = flag;
+        self
+    }
+
+    /// Emit colors as the value stored in the fl file, `Color::by_index` for colormap indices
+    /// and `Color::from_rgbi` for RGB colors, instead of named colors and `Color::from_hex`
+    pub fn raw_colors(mut self, flag: bool) -> Self {
+        self.raw_colors = flag;
+        self
+    }
+
+    /// Set a function mapping font indices above the 16 builtin fonts to the fonts the app loads,
+    /// e.g. `crate::fonts::font` for a `fn font(idx: i32) -> Font`
+    pub fn font_lookup(mut self, path: &str) -> Self {
+        self.font_lookup = Some(path.to_string());
+        self
+    }
+
+    /// Set where top-level windows open, the size stored in the fl file is kept either way
+    pub fn window_position(mut self, position: WindowPosition) -> Self {
+        self.window_position = position;
+        self
+    }
+
+    /// Generate `Fl_Table` as a `fltk_table::SmartTable`, which draws its cells itself,
+    /// instead of a `Table` needing a draw callback. Requires the fltk-table crate
+    pub fn smart_tables(mut self, flag: bool) -> Self {
+        self.smart_tables = flag;
+        self
+    }
+
+    /// Set the rows and columns of tables which don't set them with `fl2rust: rows=N cols=N`
+    /// in their fluid comment
+    pub fn table_size(mut self, rows: i32, cols: i32) -> Self {
+        self.table_size = Some((rows, cols));
+        self
+    }
+
+    /// Create a `TextBuffer` for each text display and editor, exposed next to it as `<name>_buffer`.
+    /// Enabled by default, disable it when the app attaches its own buffers
+    pub fn text_buffers(mut self, flag: bool) -> Self {
+        self.text_buffers = flag;
+        self
+    }
+
+    /// Always write generated files.
+    /// By default, files are only written when the fl file, the fl2rust version or the options changed
+    pub fn force(mut self, flag: bool) -> Self {
+        self.force = flag;
+        self
+    }
+}
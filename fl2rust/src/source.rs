@@ -0,0 +1,355 @@
+//! Index of the nodes of a fl file.
+//! The ast produced by fluid-parser doesn't keep source positions nor properties it doesn't know,
+//! so the source is scanned a second time to recover them.
+//! Nodes are keyed by their position in the ast:
+//! - `[0, function, widget...]` for free functions
+//! - `[1, widget_class, widget...]` for widget classes
+//! - `[2, class, function, widget...]` for classes
+
+use fluid_parser::lexer::Lexer;
+use fluid_parser::token::{Token, TokenType};
+use std::collections::HashMap;
+
+/// Properties which don't take a value
+const FLAGS: &[&str] = &[
+    "open",
+    "selected",
+    "hide",
+    "deactivate",
+    "divider",
+    "resizable",
+    "visible",
+    "hotspot",
+    "modal",
+    "non_modal",
+    "noborder",
+    "override",
+    "fullscreen",
+    "compact",
+    "public",
+    "private",
+    "protected",
+    "global",
+    "local",
+    "in_source",
+    "in_header",
+    "C",
+    "textmode",
+    "compressed",
+];
+
+/// Properties fluid-parser doesn't know and can't skip, as it reads their braced values as properties
+const UNPARSED_PROPS: &[&str] = &[
+    "rowheights",
+    "rowweights",
+    "rowgaps",
+    "colwidths",
+    "colweights",
+    "colgaps",
+    "min_size",
+];
+
+/// Properties referencing a file
+const FILE_PROPS: &[&str] = &["image", "deimage", "filename"];
+
+/// A property as written in the fl file
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Prop {
+    pub key: String,
+    /// The value, braced values are kept verbatim without the outer braces
+    pub value: Option<String>,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// A node of the fl file
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Node {
+    pub typ: String,
+    pub line: usize,
+    pub col: usize,
+    pub props: Vec<Prop>,
+    /// Properties stored in the `parent_properties` block
+    pub parent_props: Vec<Prop>,
+}
+
+impl Node {
+    pub fn prop(&self, key: &str) -> Option<&Prop> {
+        self.props.iter().find(|p| p.key == key)
+    }
+
+    pub fn parent_prop(&self, key: &str) -> Option<&Prop> {
+        self.parent_props.iter().find(|p| p.key == key)
+    }
+}
+
+/// Nodes of a fl file keyed by their position in the ast
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    nodes: HashMap<Vec<usize>, Node>,
+    files: Vec<String>,
+}
+
+fn is_widget(word: &str) -> bool {
+    word.starts_with("Fl_") || word == "MenuItem" || word == "Submenu"
+}
+
+struct Scanner<'a> {
+    src: &'a str,
+    tokens: Vec<Token<'a>>,
+    i: usize,
+    nodes: HashMap<Vec<usize>, Node>,
+    files: Vec<String>,
+}
+
+impl<'a> Scanner<'a> {
+    fn typ(&self) -> TokenType {
+        self.tokens
+            .get(self.i)
+            .map(|t| t.typ)
+            .unwrap_or(TokenType::Eof)
+    }
+
+    fn word(&self) -> &'a str {
+        self.tokens.get(self.i).map(|t| t.word).unwrap_or_default()
+    }
+
+    /// Consumes a braced group and returns its inner text
+    fn braced(&mut self) -> String {
+        let start = self.tokens[self.i].end;
+        let mut depth = 0;
+        while self.typ() != TokenType::Eof {
+            match self.typ() {
+                TokenType::OpenBrace => depth += 1,
+                TokenType::CloseBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let end = self.tokens[self.i].start;
+                        self.i += 1;
+                        return self.src[start..end].trim().to_string();
+                    }
+                }
+                _ => (),
+            }
+            self.i += 1;
+        }
+        self.src[start..].trim().to_string()
+    }
+
+    /// Consumes a braced property list
+    fn props(&mut self, node: &mut Node, nested: bool) -> Vec<Prop> {
+        let mut props = vec![];
+        self.i += 1;
+        while self.typ() == TokenType::Word {
+            let t = self.tokens[self.i];
+            self.i += 1;
+            let mut prop = Prop {
+                key: t.word.to_string(),
+                value: None,
+                line: t.loc.line,
+                col: t.loc.col,
+            };
+            if self.typ() == TokenType::OpenBrace {
+                if t.word == "parent_properties" && !nested {
+                    node.parent_props = self.props(node, true);
+                    continue;
+                }
+                prop.value = Some(self.braced());
+            } else if !FLAGS.contains(&t.word) && self.typ() == TokenType::Word {
+                prop.value = Some(self.word().to_string());
+                self.i += 1;
+            }
+            props.push(prop);
+        }
+        if self.typ() == TokenType::CloseBrace {
+            self.i += 1;
+        }
+        props
+    }
+
+    /// Consumes a node: `keyword name {props} {children}`, where props and children are optional.
+    /// `key` is the ast position of the node, if it has one.
+    fn node(&mut self, key: Option<Vec<usize>>) {
+        let t = self.tokens[self.i];
+        let mut node = Node {
+            typ: t.word.to_string(),
+            line: t.loc.line,
+            col: t.loc.col,
+            ..Default::default()
+        };
+        self.i += 1;
+        // name
+        if self.typ() == TokenType::OpenBrace {
+            self.braced();
+        } else if self.typ() == TokenType::Word {
+            self.i += 1;
+        }
+        if self.typ() == TokenType::OpenBrace {
+            node.props = self.props(&mut node, false);
+            for p in &node.props {
+                if let (true, Some(v)) = (FILE_PROPS.contains(&p.key.as_str()), &p.value) {
+                    self.files.push(v.clone());
+                }
+            }
+        }
+        if self.typ() == TokenType::OpenBrace {
+            self.children(key.as_deref(), &node.typ);
+        }
+        if let Some(key) = key {
+            self.nodes.insert(key, node);
+        }
+    }
+
+    /// Consumes a children block of a node of type `typ`
+    fn children(&mut self, key: Option<&[usize]>, typ: &str) {
+        self.i += 1;
+        let mut count = 0;
+        while self.typ() == TokenType::Word {
+            let word = self.word();
+            let counted = if typ == "class" {
+                word == "Function"
+            } else {
+                is_widget(word)
+            };
+            match key {
+                Some(key) if counted => {
+                    let mut child = key.to_vec();
+                    child.push(count);
+                    count += 1;
+                    self.node(Some(child));
+                }
+                _ => self.node(None),
+            }
+        }
+        if self.typ() == TokenType::CloseBrace {
+            self.i += 1;
+        }
+    }
+
+    fn scan(&mut self) {
+        let mut counts = [0; 3];
+        while self.typ() != TokenType::Eof {
+            let kind = match self.word() {
+                "Function" => Some(0),
+                "widget_class" => Some(1),
+                "class" => Some(2),
+                "comment" | "decl" | "declblock" | "code" | "codeblock" | "data" => None,
+                _ => {
+                    // project settings, either flags or `key value`
+                    self.i += 1;
+                    if self.typ() == TokenType::OpenBrace {
+                        self.braced();
+                    } else if self.typ() == TokenType::Word
+                        && !matches!(
+                            self.word(),
+                            "Function" | "widget_class" | "class" | "comment" | "decl"
+                        )
+                    {
+                        self.i += 1;
+                    }
+                    continue;
+                }
+            };
+            match kind {
+                Some(kind) => {
+                    let key = vec![kind, counts[kind]];
+                    counts[kind] += 1;
+                    self.node(Some(key));
+                }
+                None => self.node(None),
+            }
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn new(src: &str) -> Self {
+        let mut lexer = Lexer::new(src);
+        let mut tokens = vec![];
+        loop {
+            let t = lexer.next();
+            if t.typ == TokenType::Eof {
+                break;
+            }
+            tokens.push(t);
+        }
+        let mut scanner = Scanner {
+            src,
+            tokens,
+            i: 0,
+            nodes: HashMap::new(),
+            files: vec![],
+        };
+        scanner.scan();
+        Self {
+            nodes: scanner.nodes,
+            files: scanner.files,
+        }
+    }
+
+    /// Gets the node at the passed ast position
+    pub fn node(&self, key: &[usize]) -> Option<&Node> {
+        self.nodes.get(key)
+    }
+
+    /// Gets the files referenced by images and inline data, as written in the fl file
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+}
+
+/// Blanks out the properties fluid-parser fails on, keeping the position of everything else.
+/// They are read back from the `SourceMap` of the original source.
+/// fluid-parser also skips the token following `i18n_type N`, usually `class`, which is padded with a dummy token.
+pub(crate) fn parseable(src: &str) -> String {
+    let mut lexer = Lexer::new(src);
+    let mut tokens = vec![];
+    loop {
+        let t = lexer.next();
+        if t.typ == TokenType::Eof {
+            break;
+        }
+        tokens.push(t);
+    }
+    let mut out = src.as_bytes().to_vec();
+    let mut padded = None;
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        match t.typ {
+            TokenType::OpenBrace => depth += 1,
+            TokenType::CloseBrace => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 && t.word == "i18n_type" {
+            padded = tokens
+                .get(i + 1)
+                .filter(|t| t.typ == TokenType::Word)
+                .map(|t| t.end);
+        }
+        if !UNPARSED_PROPS.contains(&t.word)
+            || tokens.get(i + 1).map(|t| t.typ) != Some(TokenType::OpenBrace)
+        {
+            continue;
+        }
+        // a list of integers, anything else is e.g. code using the same word
+        let values = tokens[i + 2..]
+            .iter()
+            .take_while(|t| t.typ == TokenType::Word && t.word.parse::<i32>().is_ok())
+            .count();
+        if let Some(close) = tokens
+            .get(i + 2 + values)
+            .filter(|t| t.typ == TokenType::CloseBrace)
+        {
+            for b in &mut out[t.start..close.end] {
+                if *b != b'\n' {
+                    *b = b' ';
+                }
+            }
+        }
+    }
+    if let Some(end) = padded {
+        out.splice(end..end, b" _".iter().copied());
+    }
+    // only ascii bytes were replaced
+    String::from_utf8(out).unwrap_or_else(|_| src.to_string())
+}
@@ -0,0 +1,44 @@
+//! Stamps identifying the inputs of a generated file, so unchanged files aren't rewritten
+
+use crate::options::GeneratorOptions;
+use std::fs;
+use std::path::Path;
+
+const PREFIX: &str = "// fl2rust stamp ";
+
+/// 64 bit FNV-1a hash
+fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Stamp line of the output of a fl file, hashing its contents, the fl2rust version and the options
+pub(crate) fn stamp(content: &str, options: &GeneratorOptions) -> String {
+    let options = format!("{:?}", options.clone().force(false));
+    let mut hash = 0xcbf29ce484222325;
+    for part in [content, env!("CARGO_PKG_VERSION"), &options] {
+        hash = fnv1a(part.as_bytes(), hash);
+        // separator, so that moving bytes between parts changes the hash
+        hash = fnv1a(&[0xff], hash);
+    }
+    format!("{}{:016x}", PREFIX, hash)
+}
+
+/// Whether the file at `path` was generated with the passed stamp
+pub(crate) fn is_current(path: &Path, stamp: &str) -> bool {
+    match fs::read_to_string(path) {
+        Ok(s) => s.lines().next() == Some(stamp),
+        Err(_) => false,
+    }
+}
+
+/// Writes `contents` to `path` unless the file already has the same contents
+pub(crate) fn write_if_changed(path: &Path, contents: &str) -> std::io::Result<()> {
+    if matches!(fs::read_to_string(path), Ok(s) if s == contents) {
+        return Ok(());
+    }
+    fs::write(path, contents)
+}
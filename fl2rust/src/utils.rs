@@ -27,7 +27,7 @@ pub fn global_to_pascal(input: &str) -> String {
     if !v.is_empty() {
         v[0] = v[0].to_ascii_uppercase();
     }
-    for i in 0..v.len() - 1 {
+    for i in 0..v.len().saturating_sub(1) {
         if v[i] == '_' {
             v[i + 1] = v[i + 1].to_ascii_uppercase();
         }
@@ -57,18 +57,213 @@ pub fn strip_prefix(s: &str, pat: &str) -> Option<String> {
     }
 }
 
-pub fn fix_type(s: &str) -> &str {
-    if s.contains("Slider") {
-        return "Slider";
+/// Converts a string as stored in a fl file to its text.
+/// Fluid escapes braces and backslashes with a backslash, and reads C escapes like `\n`,
+/// a backslash before a newline continuing the line.
+pub fn fluid_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0c'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('v') => out.push('\x0b'),
+            Some('\n') => (),
+            Some('x') => {
+                let mut v = 0;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|d| d.to_digit(16)) {
+                        Some(d) => {
+                            v = v * 16 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.extend(char::from_u32(v));
+            }
+            Some(d @ '0'..='7') => {
+                let mut v = d as u32 - '0' as u32;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|d| d.to_digit(8)) {
+                        Some(d) => {
+                            v = v * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.extend(char::from_u32(v));
+            }
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
     }
-    if s.contains("Browser") {
-        return "Browser";
+    out
+}
+
+/// Escapes a label for use in a menu path, where `/` separates submenus,
+/// a leading `_` adds a divider and `\\` escapes the next character
+pub fn menu_path_segment(label: &str) -> String {
+    let mut s = String::with_capacity(label.len());
+    for c in label.chars() {
+        if matches!(c, '\\' | '/' | '_') {
+            s.push('\\');
+        }
+        s.push(c);
     }
-    if s.contains("Button") {
-        return "Button";
+    s
+}
+
+/// A setting of a directive line, with a `None` value for bare flags
+pub type Directive = (String, Option<String>);
+
+/// Splits a widget comment into its text and the settings of its `fl2rust:` lines,
+/// written as `key` or `key=value`, values with spaces being double quoted.
+/// e.g. `fl2rust: rows=10 col_header root_label="My files"`
+pub fn split_directives(comment: &str) -> (String, Vec<Directive>) {
+    let mut text = vec![];
+    let mut directives = vec![];
+    for line in comment.lines() {
+        let rest = match line.trim_start().strip_prefix("fl2rust:") {
+            Some(rest) => rest,
+            None => {
+                text.push(line);
+                continue;
+            }
+        };
+        let mut chars = rest.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let mut key = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+                key.push(c);
+            }
+            if key.is_empty() {
+                break;
+            }
+            let value = if chars.next_if_eq(&'=').is_some() {
+                let mut value = String::new();
+                if chars.next_if_eq(&'"').is_some() {
+                    for c in chars.by_ref().take_while(|c| *c != '"') {
+                        value.push(c);
+                    }
+                } else {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                }
+                Some(value)
+            } else {
+                None
+            };
+            directives.push((key, value));
+        }
     }
-    if s.contains("Input") {
-        return "Input";
+    (text.join("\n").trim().to_string(), directives)
+}
+
+/// Converts a label to a snake_case identifier, `None` if nothing usable remains
+pub fn label_to_snake(label: &str) -> Option<String> {
+    let mut s = String::new();
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            s.push(c.to_ascii_lowercase());
+        } else if !s.is_empty() && !s.ends_with('_') {
+            s.push('_');
+        }
+    }
+    let s = s.trim_end_matches('_');
+    match s.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// Converts a CamelCase or kebab-case name to snake_case
+pub fn to_snake_case(name: &str) -> String {
+    let mut s = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && prev_lower {
+            s.push('_');
+        }
+        if c.is_ascii_alphanumeric() {
+            s.push(c.to_ascii_lowercase());
+        } else if !s.ends_with('_') {
+            s.push('_');
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
     }
     s
 }
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+pub fn is_valid_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+    s != "_" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !KEYWORDS.contains(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fluid_strings_are_unescaped() {
+        assert_eq!(fluid_string(r"a\{b\} \\ c"), r"a{b} \ c");
+        assert_eq!(fluid_string(r"one\ntwo\tthree"), "one\ntwo\tthree");
+        assert_eq!(fluid_string("line\\\ncontinued"), "linecontinued");
+        assert_eq!(fluid_string(r"\x41\101\x4g\0"), "AA\x04g\0");
+        assert_eq!(fluid_string(r"\#include"), "#include");
+        assert_eq!(fluid_string("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn menu_path_segments_are_escaped() {
+        assert_eq!(menu_path_segment("Open"), "Open");
+        assert_eq!(menu_path_segment("Save/Export"), "Save\\/Export");
+        assert_eq!(menu_path_segment("_a\\b"), "\\_a\\\\b");
+        // only `add_choice` splits on `|`, menu items are added with `add`
+        assert_eq!(menu_path_segment("Cut|Paste"), "Cut|Paste");
+    }
+
+    #[test]
+    fn directives_are_split_from_comments() {
+        let (text, directives) = split_directives(
+            "Stock levels\nfl2rust: rows=20 col_header root_label=\"My files\"\n  fl2rust: select=multi",
+        );
+        assert_eq!(text, "Stock levels");
+        assert_eq!(
+            directives,
+            [
+                ("rows".to_string(), Some("20".to_string())),
+                ("col_header".to_string(), None),
+                ("root_label".to_string(), Some("My files".to_string())),
+                ("select".to_string(), Some("multi".to_string())),
+            ]
+        );
+        let (text, directives) = split_directives("Saves the file\nto disk");
+        assert_eq!(text, "Saves the file\nto disk");
+        assert!(directives.is_empty());
+        assert_eq!(split_directives("fl2rust:"), (String::new(), vec![]));
+    }
+}
@@ -0,0 +1,271 @@
+//! Registry of the widgets fluid 1.3 and 1.4 can emit and their fltk-rs equivalents
+
+use std::ops::BitOr;
+
+/// The kind of a widget, deciding how its children and properties are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WidgetCategory {
+    /// A plain widget
+    Widget,
+    /// A button, whose value is a boolean
+    Button,
+    /// A group, which is ended after adding its children
+    Group,
+    /// A window, which is also a group
+    Window,
+    /// A menu, whose children are added as menu items
+    Menu,
+    /// A valuator, which has a range and a numeric value
+    Valuator,
+    /// A text input or output, whose value is a string
+    Input,
+    /// A browser
+    Browser,
+    /// A text display or editor
+    Text,
+}
+
+impl WidgetCategory {
+    /// Whether widgets of this category are ended after adding their children
+    pub fn is_group(self) -> bool {
+        matches!(
+            self,
+            WidgetCategory::Group | WidgetCategory::Window | WidgetCategory::Menu
+        )
+    }
+
+    /// Setters provided by the trait widgets of this category implement,
+    /// e.g. `ButtonExt` for buttons
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            WidgetCategory::Widget | WidgetCategory::Group => Capabilities::NONE,
+            WidgetCategory::Button => {
+                Capabilities::VALUE | Capabilities::SHORTCUT | Capabilities::DOWN_FRAME
+            }
+            WidgetCategory::Window => Capabilities::WINDOW,
+            WidgetCategory::Menu => Capabilities::TEXT | Capabilities::DOWN_FRAME,
+            WidgetCategory::Valuator => {
+                Capabilities::RANGE | Capabilities::STEP | Capabilities::VALUE
+            }
+            WidgetCategory::Input => Capabilities::TEXT | Capabilities::VALUE,
+            WidgetCategory::Browser | WidgetCategory::Text => Capabilities::TEXT,
+        }
+    }
+}
+
+/// Groups of setters a widget supports beyond those of `WidgetExt`.
+/// Properties needing a missing capability are dropped with a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    /// `textfont`, `textsize` and `textcolor`
+    pub const TEXT: Self = Self(1);
+    /// `minimum` and `maximum`
+    pub const RANGE: Self = Self(1 << 1);
+    /// `step`
+    pub const STEP: Self = Self(1 << 2);
+    /// `slider_size`
+    pub const SLIDER_SIZE: Self = Self(1 << 3);
+    /// `value`
+    pub const VALUE: Self = Self(1 << 4);
+    /// `shortcut`
+    pub const SHORTCUT: Self = Self(1 << 5);
+    /// `down_box`
+    pub const DOWN_FRAME: Self = Self(1 << 6);
+    /// `gap`
+    pub const PAD: Self = Self(1 << 7);
+    /// `margin` and `margins`
+    pub const MARGINS: Self = Self(1 << 8);
+    /// `size_range`, `modal`, `non_modal`, `noborder`, `override`, `fullscreen`, `xclass`,
+    /// resizable windows and icons
+    pub const WINDOW: Self = Self(1 << 9);
+    /// `dimensions`, `margin`, the row and column sizes of grids and the placement of their children
+    pub const GRID: Self = Self(1 << 10);
+    /// `fixed_size_tuples`, the children of flexes with a fixed size
+    pub const FIXED: Self = Self(1 << 11);
+    /// Rows, columns and headers of tables, set with comment directives
+    pub const TABLE: Self = Self(1 << 12);
+    /// Root and connectors of trees set with comment directives, text properties styling the items
+    pub const TREE: Self = Self(1 << 13);
+    /// Text widgets showing a `TextBuffer`, created along with them
+    pub const BUFFER: Self = Self(1 << 14);
+    /// Menu items added to the widget's menu button, for widgets that aren't menus themselves
+    pub const MENU: Self = Self(1 << 15);
+    /// `gap`, set as the spacing of packs
+    pub const SPACING: Self = Self(1 << 16);
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Combines two sets of capabilities
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Whether all of `other` is supported
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+/// A widget fluid can emit
+#[derive(Debug)]
+pub(crate) struct WidgetInfo {
+    /// The fluid class, e.g. `Fl_Button`
+    pub fluid: &'static str,
+    /// Path of the fltk-rs type, relative to the fltk crate when it isn't glob-imported
+    pub path: &'static str,
+    /// Enum set by the `type` property
+    pub type_enum: Option<&'static str>,
+    pub category: WidgetCategory,
+    /// Capabilities from inherent methods, added to those of the category
+    pub extra: Capabilities,
+}
+
+const fn w(
+    fluid: &'static str,
+    path: &'static str,
+    type_enum: Option<&'static str>,
+    category: WidgetCategory,
+) -> WidgetInfo {
+    WidgetInfo {
+        fluid,
+        path,
+        type_enum,
+        category,
+        extra: Capabilities::NONE,
+    }
+}
+
+impl WidgetInfo {
+    const fn with(self, extra: Capabilities) -> Self {
+        Self { extra, ..self }
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.category.capabilities() | self.extra
+    }
+}
+
+use WidgetCategory::*;
+
+const WINDOW: Option<&str> = Some("WindowType");
+const BUTTON: Option<&str> = Some("ButtonType");
+const SLIDER: Option<&str> = Some("SliderType");
+const DIAL: Option<&str> = Some("DialType");
+const INPUT: Option<&str> = Some("InputType");
+const BROWSER: Option<&str> = Some("BrowserType");
+
+const SLIDER_SIZE: Capabilities = Capabilities::SLIDER_SIZE;
+const VALUE_TEXT: Capabilities = Capabilities::SLIDER_SIZE.union(Capabilities::TEXT);
+
+pub(crate) const WIDGETS: &[WidgetInfo] = &[
+    // windows
+    w("Fl_Window", "Window", WINDOW, Window),
+    w("Fl_Double_Window", "DoubleWindow", WINDOW, Window),
+    w("Fl_Overlay_Window", "OverlayWindow", WINDOW, Window),
+    // groups
+    w("Fl_Group", "Group", None, Group),
+    w("Fl_Pack", "Pack", Some("PackType"), Group).with(Capabilities::SPACING),
+    w("Fl_Flex", "Flex", Some("FlexType"), Group).with(
+        Capabilities::PAD
+            .union(Capabilities::MARGINS)
+            .union(Capabilities::FIXED),
+    ),
+    w("Fl_Grid", "Grid", None, Group).with(Capabilities::GRID),
+    w("Fl_Tabs", "Tabs", None, Group),
+    w("Fl_Scroll", "Scroll", Some("ScrollType"), Group),
+    w("Fl_Tile", "Tile", None, Group),
+    w("Fl_Wizard", "Wizard", None, Group),
+    w("Fl_Table", "Table", None, Group).with(Capabilities::TABLE),
+    w(
+        "Fl_Table_Row",
+        "TableRow",
+        Some("TableRowSelectMode"),
+        Group,
+    )
+    .with(Capabilities::TABLE),
+    w("Fl_Tree", "Tree", None, Group).with(Capabilities::TREE),
+    // menus
+    w("Fl_Menu_Bar", "MenuBar", None, Menu),
+    w("Fl_Sys_Menu_Bar", "SysMenuBar", None, Menu),
+    w("Fl_Menu_Button", "MenuButton", Some("MenuButtonType"), Menu),
+    w("Fl_Choice", "Choice", None, Menu).with(Capabilities::VALUE),
+    w("Fl_Input_Choice", "InputChoice", None, Input).with(Capabilities::MENU),
+    // buttons
+    w("Fl_Button", "Button", BUTTON, Button),
+    w("Fl_Return_Button", "ReturnButton", BUTTON, Button),
+    w("Fl_Light_Button", "LightButton", BUTTON, Button),
+    w("Fl_Check_Button", "CheckButton", BUTTON, Button),
+    w("Fl_Round_Button", "RoundButton", BUTTON, Button),
+    w("Fl_Repeat_Button", "RepeatButton", BUTTON, Button),
+    w("Fl_Toggle_Button", "ToggleButton", BUTTON, Button),
+    w("Fl_Radio_Button", "RadioButton", BUTTON, Button),
+    w("Fl_Radio_Light_Button", "RadioLightButton", BUTTON, Button),
+    w("Fl_Radio_Round_Button", "RadioRoundButton", BUTTON, Button),
+    w("Fl_Shortcut_Button", "ShortcutButton", BUTTON, Button),
+    // valuators
+    w("Fl_Slider", "Slider", SLIDER, Valuator).with(SLIDER_SIZE),
+    w("Fl_Nice_Slider", "NiceSlider", SLIDER, Valuator).with(SLIDER_SIZE),
+    w("Fl_Fill_Slider", "FillSlider", SLIDER, Valuator).with(SLIDER_SIZE),
+    w("Fl_Hor_Slider", "HorSlider", SLIDER, Valuator).with(SLIDER_SIZE),
+    w("Fl_Hor_Fill_Slider", "HorFillSlider", SLIDER, Valuator).with(SLIDER_SIZE),
+    w("Fl_Hor_Nice_Slider", "HorNiceSlider", SLIDER, Valuator).with(SLIDER_SIZE),
+    w("Fl_Value_Slider", "ValueSlider", SLIDER, Valuator).with(VALUE_TEXT),
+    w("Fl_Hor_Value_Slider", "HorValueSlider", SLIDER, Valuator).with(VALUE_TEXT),
+    w("Fl_Scrollbar", "Scrollbar", Some("ScrollbarType"), Valuator).with(SLIDER_SIZE),
+    w("Fl_Dial", "Dial", DIAL, Valuator),
+    w("Fl_Line_Dial", "LineDial", DIAL, Valuator),
+    w("Fl_Fill_Dial", "FillDial", DIAL, Valuator),
+    w("Fl_Roller", "Roller", SLIDER, Valuator),
+    w("Fl_Counter", "Counter", Some("CounterType"), Valuator),
+    w("Fl_Adjuster", "Adjuster", None, Valuator),
+    w("Fl_Value_Input", "ValueInput", None, Valuator).with(Capabilities::TEXT),
+    w("Fl_Value_Output", "ValueOutput", None, Valuator).with(Capabilities::TEXT),
+    // `Spinner::set_step` doesn't take the divisor of `ValuatorExt::set_step`
+    w("Fl_Spinner", "Spinner", INPUT, Widget).with(
+        Capabilities::RANGE
+            .union(Capabilities::VALUE)
+            .union(Capabilities::TEXT),
+    ),
+    // inputs
+    w("Fl_Input", "Input", INPUT, Input),
+    w("Fl_Float_Input", "FloatInput", INPUT, Input),
+    w("Fl_Int_Input", "IntInput", INPUT, Input),
+    w("Fl_Multiline_Input", "MultilineInput", INPUT, Input),
+    w("Fl_Secret_Input", "SecretInput", INPUT, Input),
+    w("Fl_File_Input", "FileInput", INPUT, Input),
+    w("Fl_Output", "Output", None, Input),
+    w("Fl_Multiline_Output", "MultilineOutput", None, Input),
+    // browsers
+    w("Fl_Browser", "Browser", BROWSER, Browser),
+    w("Fl_Select_Browser", "SelectBrowser", BROWSER, Browser),
+    w("Fl_Hold_Browser", "HoldBrowser", BROWSER, Browser),
+    w("Fl_Multi_Browser", "MultiBrowser", BROWSER, Browser),
+    w("Fl_File_Browser", "FileBrowser", BROWSER, Browser),
+    w("Fl_Check_Browser", "CheckBrowser", BROWSER, Browser),
+    // text
+    w("Fl_Text_Display", "TextDisplay", None, Text).with(Capabilities::BUFFER),
+    w("Fl_Text_Editor", "TextEditor", None, Text).with(Capabilities::BUFFER),
+    w("Fl_Simple_Terminal", "SimpleTerminal", None, Text),
+    w("Fl_Terminal", "fltk::terminal::Terminal", None, Widget).with(Capabilities::TEXT),
+    // misc
+    w("Fl_Box", "Frame", None, Widget),
+    w("Fl_Clock", "Clock", None, Widget),
+    w("Fl_Progress", "Progress", None, Widget).with(Capabilities::RANGE.union(Capabilities::VALUE)),
+    w("Fl_Help_View", "HelpView", None, Widget).with(Capabilities::TEXT),
+    w("Fl_Chart", "Chart", None, Widget),
+];
+
+/// Looks up a fluid widget class in the registry
+pub(crate) fn lookup(fluid: &str) -> Option<&'static WidgetInfo> {
+    WIDGETS.iter().find(|w| w.fluid == fluid)
+}